@@ -0,0 +1,275 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{EventTarget, HtmlCanvasElement};
+use weblog::console_error;
+
+use super::{
+    clock::Clock,
+    input::{
+        web::{poll_gamepads, InputListeners},
+        Input,
+    },
+};
+
+/// State shared with the `App` callbacks.
+pub struct AppContext {
+    pub canvas_id: String,
+    /// Pass it to `Renderer::from_canvas` rather than looking it up again.
+    pub canvas: HtmlCanvasElement,
+    /// Updated at the start of each frame, before `App::fixed_update`.
+    pub input: Rc<RefCell<Input>>,
+    /// Drives the fixed updates. Its time scale and pause apply to the `dt`
+    /// given to the app, `alpha()` interpolates rendering between fixed steps.
+    pub clock: Clock,
+
+    stop_requested: bool,
+}
+
+impl AppContext {
+    /// Stops the runner at the end of the current frame.
+    pub fn stop(&mut self) {
+        self.stop_requested = true;
+    }
+}
+
+/// An application driven by a `Runner`. Every method has a default empty
+/// implementation.
+pub trait App {
+    fn init(&mut self, _context: &mut AppContext) -> Result<(), JsValue> {
+        Ok(())
+    }
+
+    /// Called once per frame with the elapsed time in seconds.
+    fn update(&mut self, _context: &mut AppContext, _dt: f32) {}
+
+    /// Called zero or more times per frame, `dt` is always `Clock::fixed_step`.
+    fn fixed_update(&mut self, _context: &mut AppContext, _dt: f32) {}
+
+    fn render(&mut self, _context: &mut AppContext) -> Result<(), JsValue> {
+        Ok(())
+    }
+
+    /// Called after `init` and whenever the window is resized.
+    fn resize(&mut self, _context: &mut AppContext, _width: f64, _height: f64) {}
+
+    fn shutdown(&mut self, _context: &mut AppContext) {}
+}
+
+type Listener = Closure<dyn FnMut(web_sys::Event)>;
+type FrameCell = Rc<RefCell<Option<Closure<dyn FnMut()>>>>;
+
+struct RunnerState {
+    app: Box<dyn App>,
+    context: AppContext,
+
+    paused: bool,
+    stopped: bool,
+    request_id: Option<i32>,
+
+    listeners: Vec<(EventTarget, &'static str, Listener)>,
+    input_listeners: Option<InputListeners>,
+}
+
+/// Owns the `requestAnimationFrame` loop of an `App`. The loop keeps running
+/// after this handle is dropped, until `stop` is called or the app requests it
+/// through `AppContext::stop`. The loop pauses while the page is hidden.
+///
+/// The frame and DOM listener closures hold clones of the runner. `stop` is
+/// what breaks these reference cycles, the app and its state are never freed
+/// otherwise.
+#[derive(Clone)]
+pub struct Runner {
+    state: Rc<RefCell<RunnerState>>,
+    frame: FrameCell,
+}
+
+impl Runner {
+    pub fn start(app: Box<dyn App>, canvas_id: &str) -> Result<Runner, JsValue> {
+        let window = web_sys::window().expect("No global window object");
+        let document = window.document().expect("Should have a document on window");
+        let canvas = document
+            .get_element_by_id(canvas_id)
+            .expect("No canvas found")
+            .dyn_into::<HtmlCanvasElement>()?;
+
+        let input = Rc::new(RefCell::new(Input::new()));
+        let context = AppContext {
+            canvas_id: canvas_id.to_string(),
+            canvas: canvas.clone(),
+            input: input.clone(),
+            clock: Clock::browser(),
+            stop_requested: false,
+        };
+
+        let runner = Runner {
+            state: Rc::new(RefCell::new(RunnerState {
+                app,
+                context,
+                paused: false,
+                stopped: false,
+                request_id: None,
+                listeners: Vec::new(),
+                input_listeners: Some(InputListeners::attach(&input, &canvas)?),
+            })),
+            frame: Rc::new(RefCell::new(None)),
+        };
+
+        {
+            let mut state = runner.state.borrow_mut();
+            let RunnerState { app, context, .. } = &mut *state;
+            app.init(context)?;
+            app.resize(context, window_width(), window_height());
+        }
+
+        let r = runner.clone();
+        runner.listen(&window, "resize", move |_event| {
+            let mut state = r.state.borrow_mut();
+            let RunnerState { app, context, .. } = &mut *state;
+            app.resize(context, window_width(), window_height());
+        })?;
+
+        let r = runner.clone();
+        let documentc = document.clone();
+        runner.listen(&document, "visibilitychange", move |_event| {
+            if documentc.hidden() {
+                r.pause();
+            } else {
+                r.resume();
+            }
+        })?;
+
+        let r = runner.clone();
+        *runner.frame.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+            r.frame();
+        }) as Box<dyn FnMut()>));
+
+        runner.request_frame();
+
+        Ok(runner)
+    }
+
+    fn listen<F>(&self, target: &EventTarget, name: &'static str, f: F) -> Result<(), JsValue>
+    where
+        F: FnMut(web_sys::Event) + 'static,
+    {
+        let closure = Closure::wrap(Box::new(f) as Box<dyn FnMut(_)>);
+        target.add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())?;
+        self.state.borrow_mut().listeners.push((target.clone(), name, closure));
+        Ok(())
+    }
+
+    fn request_frame(&self) {
+        let id = web_sys::window()
+            .unwrap()
+            .request_animation_frame(self.frame.borrow().as_ref().unwrap().as_ref().unchecked_ref())
+            .expect("should register 'requestAnimationFrame'");
+        self.state.borrow_mut().request_id = Some(id);
+    }
+
+    fn frame(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.request_id = None;
+            if state.paused || state.stopped {
+                return;
+            }
+
+            let RunnerState { app, context, .. } = &mut *state;
+            let tick = context.clock.tick();
+
+            {
+                let mut input = context.input.borrow_mut();
+                poll_gamepads(&mut input);
+                input.update();
+            }
+
+            let step = context.clock.fixed_step;
+            for _ in 0..tick.fixed_steps {
+                app.fixed_update(context, step);
+            }
+
+            app.update(context, tick.dt);
+            if let Err(err) = app.render(context) {
+                console_error!(err);
+            }
+
+            if !context.stop_requested {
+                drop(state);
+                self.request_frame();
+                return;
+            }
+        }
+        self.stop();
+    }
+
+    pub fn pause(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.paused || state.stopped {
+            return;
+        }
+        state.paused = true;
+        if let Some(id) = state.request_id.take() {
+            let _ = web_sys::window().unwrap().cancel_animation_frame(id);
+        }
+    }
+
+    pub fn resume(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.paused || state.stopped {
+                return;
+            }
+            state.paused = false;
+            // do not count the paused time as elapsed
+            state.context.clock.reset_delta();
+        }
+        self.request_frame();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    /// Calls `App::shutdown`, removes the DOM listeners and ends the loop.
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.stopped {
+            return;
+        }
+        state.stopped = true;
+        if let Some(id) = state.request_id.take() {
+            let _ = web_sys::window().unwrap().cancel_animation_frame(id);
+        }
+
+        let RunnerState { app, context, .. } = &mut *state;
+        app.shutdown(context);
+
+        for (target, name, closure) in state.listeners.drain(..) {
+            let _ = target.remove_event_listener_with_callback(name, closure.as_ref().unchecked_ref());
+            defer_drop(closure);
+        }
+        state.input_listeners = None;
+
+        // the frame closure may be the caller, it is released once it returns
+        if let Some(frame) = self.frame.borrow_mut().take() {
+            defer_drop(frame);
+        }
+    }
+}
+
+/// Drops `value` from a later task, for closures that may be running.
+fn defer_drop<T: 'static>(value: T) {
+    let release = Closure::once_into_js(move || drop(value));
+    let _ = web_sys::window()
+        .unwrap()
+        .set_timeout_with_callback(release.unchecked_ref());
+}
+
+fn window_width() -> f64 {
+    web_sys::window().unwrap().inner_width().unwrap().as_f64().unwrap()
+}
+
+fn window_height() -> f64 {
+    web_sys::window().unwrap().inner_height().unwrap().as_f64().unwrap()
+}
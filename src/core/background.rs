@@ -0,0 +1,63 @@
+use wasm_bindgen::JsValue;
+
+use super::{
+    fullscreen::{FullscreenTriangle, FULLSCREEN_VERTEX_SHADER},
+    material::Material,
+    renderer::RenderingContext,
+    scene::Background,
+    skybox::Skybox,
+    uniform::UniformSet,
+};
+
+/// Draws the non color backgrounds of a scene with full screen triangles.
+pub struct BackgroundRenderer {
+    skybox: Skybox,
+    gradient: Material,
+    texture: Material,
+}
+
+impl Default for BackgroundRenderer {
+    fn default() -> BackgroundRenderer {
+        BackgroundRenderer::new()
+    }
+}
+
+impl BackgroundRenderer {
+    pub fn new() -> BackgroundRenderer {
+        BackgroundRenderer {
+            skybox: Skybox::new(),
+            gradient: Material::with_shaders(
+                FULLSCREEN_VERTEX_SHADER,
+                include_str!("shaders/gradient_fragment.glsl"),
+            ),
+            texture: Material::with_shaders(FULLSCREEN_VERTEX_SHADER, include_str!("shaders/texture_fragment.glsl")),
+        }
+    }
+
+    /// Must be called right after the clear, nothing is drawn for
+    /// `Background::Color` which only sets the clear color.
+    pub fn draw(
+        &mut self,
+        context: &RenderingContext,
+        fullscreen: &FullscreenTriangle,
+        background: &Background,
+    ) -> Result<(), JsValue> {
+        let material = match background {
+            Background::Color(_) => return Ok(()),
+            Background::Gradient { top, bottom } => {
+                self.gradient.set_uniform("topColor", *top);
+                self.gradient.set_uniform("bottomColor", *bottom);
+                &mut self.gradient
+            }
+            Background::Texture(texture) => {
+                self.texture.set_texture("map", texture);
+                &mut self.texture
+            }
+            Background::Skybox(texture) => {
+                return self.skybox.draw(context, fullscreen, &mut texture.borrow_mut());
+            }
+        };
+        let gl = context.gl;
+        fullscreen.draw(gl, context.programs, context.max_texture_units, material, &UniformSet::new())
+    }
+}
@@ -0,0 +1,234 @@
+/// Current time in milliseconds, from an arbitrary origin.
+pub trait TimeSource {
+    fn now(&self) -> f64;
+}
+
+/// `performance.now()` of the browser window.
+pub struct PerformanceTime {
+    performance: web_sys::Performance,
+}
+
+impl Default for PerformanceTime {
+    fn default() -> PerformanceTime {
+        PerformanceTime::new()
+    }
+}
+
+impl PerformanceTime {
+    pub fn new() -> PerformanceTime {
+        let window = web_sys::window().expect("No global window object");
+        PerformanceTime {
+            performance: window.performance().expect("performance should be available"),
+        }
+    }
+}
+
+impl TimeSource for PerformanceTime {
+    fn now(&self) -> f64 {
+        self.performance.now()
+    }
+}
+
+/// Result of one `Clock::tick`, times are in seconds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tick {
+    /// Scaled time since the previous tick, zero while paused.
+    pub dt: f32,
+    /// Number of fixed updates to run this frame.
+    pub fixed_steps: u32,
+    /// How far the simulation is between the last fixed step and the next
+    /// one, in [0, 1), for interpolating the rendered state.
+    pub alpha: f32,
+}
+
+/// Frame clock with a fixed timestep accumulator.
+pub struct Clock {
+    source: Box<dyn TimeSource>,
+
+    /// Step of the fixed updates, in seconds.
+    pub fixed_step: f32,
+    /// Multiplies the elapsed time, 0.5 runs at half speed.
+    pub time_scale: f32,
+    /// Longest unscaled frame time accounted for, in seconds. Longer frames,
+    /// e.g. after a breakpoint, are clamped to avoid a burst of fixed steps.
+    pub max_frame_time: f32,
+    /// Fixed steps per tick before the remaining time is dropped.
+    pub max_steps: u32,
+
+    paused: bool,
+    last_time: Option<f64>,
+    accumulator: f64,
+    elapsed: f64,
+    alpha: f32,
+}
+
+impl Clock {
+    pub fn new(source: Box<dyn TimeSource>) -> Clock {
+        Clock {
+            source,
+            fixed_step: 1.0 / 60.0,
+            time_scale: 1.0,
+            max_frame_time: 0.25,
+            max_steps: 8,
+            paused: false,
+            last_time: None,
+            accumulator: 0.0,
+            elapsed: 0.0,
+            alpha: 0.0,
+        }
+    }
+
+    /// A clock reading `performance.now()`.
+    pub fn browser() -> Clock {
+        Clock::new(Box::new(PerformanceTime::new()))
+    }
+
+    pub fn tick(&mut self) -> Tick {
+        let now = self.source.now();
+        let frame_time = self.last_time.map_or(0.0, |last| ((now - last) / 1000.0).max(0.0));
+        self.last_time = Some(now);
+
+        if self.paused {
+            return Tick {
+                dt: 0.0,
+                fixed_steps: 0,
+                alpha: self.alpha,
+            };
+        }
+
+        let dt = frame_time.min(self.max_frame_time as f64) * self.time_scale as f64;
+        self.elapsed += dt;
+        self.accumulator += dt;
+
+        let step = self.fixed_step as f64;
+        let mut fixed_steps = 0;
+        while self.accumulator >= step && fixed_steps < self.max_steps {
+            self.accumulator -= step;
+            fixed_steps += 1;
+        }
+        if self.accumulator >= step {
+            // too far behind, give up on catching up
+            self.accumulator %= step;
+        }
+        self.alpha = (self.accumulator / step) as f32;
+
+        Tick {
+            dt: dt as f32,
+            fixed_steps,
+            alpha: self.alpha,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Forgets the time of the last tick, so that the time until the next one
+    /// is not counted. Used when the frame loop was suspended.
+    pub fn reset_delta(&mut self) {
+        self.last_time = None;
+    }
+
+    /// Scaled time accumulated since the clock was created, in seconds.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// A time source advanced by hand, clones share the same time.
+    #[derive(Clone)]
+    struct ManualTime {
+        time: Rc<Cell<f64>>,
+    }
+
+    impl ManualTime {
+        fn new() -> ManualTime {
+            ManualTime {
+                time: Rc::new(Cell::new(0.0)),
+            }
+        }
+
+        fn advance(&self, milliseconds: f64) {
+            self.time.set(self.time.get() + milliseconds);
+        }
+    }
+
+    impl TimeSource for ManualTime {
+        fn now(&self) -> f64 {
+            self.time.get()
+        }
+    }
+
+    fn clock() -> (Clock, ManualTime) {
+        let time = ManualTime::new();
+        let mut clock = Clock::new(Box::new(time.clone()));
+        clock.fixed_step = 0.01;
+        clock.tick();
+        (clock, time)
+    }
+
+    #[test]
+    fn test_fixed_steps() {
+        let (mut clock, time) = clock();
+
+        time.advance(25.0);
+        let tick = clock.tick();
+        assert_eq!(tick.fixed_steps, 2);
+        assert!((tick.dt - 0.025).abs() < 1e-6);
+        assert!((tick.alpha - 0.5).abs() < 1e-4);
+
+        time.advance(5.0);
+        assert_eq!(clock.tick().fixed_steps, 1);
+    }
+
+    #[test]
+    fn test_scale_and_pause() {
+        let (mut clock, time) = clock();
+        clock.time_scale = 0.5;
+
+        time.advance(40.0);
+        let tick = clock.tick();
+        assert!((tick.dt - 0.02).abs() < 1e-6);
+        assert_eq!(tick.fixed_steps, 2);
+
+        clock.pause();
+        time.advance(100.0);
+        assert_eq!(clock.tick().fixed_steps, 0);
+
+        // the paused time is not accumulated
+        clock.resume();
+        time.advance(10.0);
+        assert_eq!(clock.tick().fixed_steps, 0);
+        assert!((clock.elapsed() - 0.025).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_clamping() {
+        let (mut clock, time) = clock();
+        clock.max_steps = 4;
+
+        time.advance(5000.0);
+        let tick = clock.tick();
+        assert!((tick.dt - 0.25).abs() < 1e-6);
+        assert_eq!(tick.fixed_steps, 4);
+        assert!(tick.alpha < 1.0);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+
+use cgmath::{vec3, InnerSpace, Quaternion, Rad, Rotation, Rotation3, Vector3};
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlCanvasElement, KeyboardEvent, MouseEvent};
+
+use crate::core::{graph::Node, input::web::EventListeners, object3d::Object3d};
+
+const MAX_PITCH: f32 = PI / 2.0 - 0.01;
+
+/// First person controls: WASD moves, Q/E moves down/up, Shift sprints and
+/// the mouse looks around while the pointer is locked on the canvas.
+pub struct FlyControls {
+    /// Units per second.
+    pub movement_speed: f32,
+    pub sprint_multiplier: f32,
+    /// Radians per pixel of mouse movement.
+    pub look_speed: f32,
+    /// When false the mouse looks around while a button is held instead.
+    pub use_pointer_lock: bool,
+
+    pub pointer_locked: bool,
+
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+    dragging: bool,
+
+    yaw_delta: f32,
+    pitch_delta: f32,
+}
+
+impl Default for FlyControls {
+    fn default() -> FlyControls {
+        FlyControls::new()
+    }
+}
+
+impl FlyControls {
+    pub fn new() -> FlyControls {
+        FlyControls {
+            movement_speed: 5.0,
+            sprint_multiplier: 3.0,
+            look_speed: 0.002,
+            use_pointer_lock: true,
+            pointer_locked: false,
+            forward: false,
+            backward: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            sprint: false,
+            dragging: false,
+            yaw_delta: 0.0,
+            pitch_delta: 0.0,
+        }
+    }
+
+    fn set_key(&mut self, code: &str, pressed: bool) {
+        match code {
+            "KeyW" | "ArrowUp" => self.forward = pressed,
+            "KeyS" | "ArrowDown" => self.backward = pressed,
+            "KeyA" | "ArrowLeft" => self.left = pressed,
+            "KeyD" | "ArrowRight" => self.right = pressed,
+            "KeyE" | "Space" => self.up = pressed,
+            "KeyQ" | "KeyC" => self.down = pressed,
+            "ShiftLeft" | "ShiftRight" => self.sprint = pressed,
+            _ => {}
+        }
+    }
+
+    /// `code` is a `KeyboardEvent.code` value such as `"KeyW"`.
+    pub fn key_down(&mut self, code: &str) {
+        self.set_key(code, true);
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        self.set_key(code, false);
+    }
+
+    pub fn mouse_down(&mut self) {
+        self.dragging = true;
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.dragging = false;
+    }
+
+    pub fn mouse_move(&mut self, movement_x: f32, movement_y: f32) {
+        let looking = if self.use_pointer_lock {
+            self.pointer_locked
+        } else {
+            self.dragging
+        };
+        if looking {
+            self.yaw_delta -= movement_x * self.look_speed;
+            self.pitch_delta -= movement_y * self.look_speed;
+        }
+    }
+
+    /// Releases every key, e.g. when the window loses focus.
+    pub fn reset(&mut self) {
+        *self = FlyControls {
+            movement_speed: self.movement_speed,
+            sprint_multiplier: self.sprint_multiplier,
+            look_speed: self.look_speed,
+            use_pointer_lock: self.use_pointer_lock,
+            pointer_locked: self.pointer_locked,
+            ..FlyControls::new()
+        };
+    }
+
+    /// Moves and rotates `camera`, `dt` being the elapsed time in seconds
+    /// since the previous update.
+    pub fn update(&mut self, camera: &Rc<Node<Object3d>>, dt: f32) {
+        let mut object = camera.value.borrow_mut();
+        let transform = &mut object.transform;
+
+        // yaw around the world up axis, pitch around the local right axis
+        let forward = transform.quaternion.rotate_vector(-Vector3::unit_z());
+        let pitch = forward.y.clamp(-1.0, 1.0).asin();
+        let pitch_delta = (pitch + self.pitch_delta).clamp(-MAX_PITCH, MAX_PITCH) - pitch;
+        transform.quaternion = (Quaternion::from_angle_y(Rad(self.yaw_delta))
+            * transform.quaternion
+            * Quaternion::from_angle_x(Rad(pitch_delta)))
+        .normalize();
+        self.yaw_delta = 0.0;
+        self.pitch_delta = 0.0;
+
+        let direction = vec3(
+            axis(self.right, self.left),
+            axis(self.up, self.down),
+            axis(self.backward, self.forward),
+        );
+        if direction.magnitude2() > 0.0 {
+            let mut speed = self.movement_speed * dt;
+            if self.sprint {
+                speed *= self.sprint_multiplier;
+            }
+            transform.translate_on_axis(direction, speed);
+        }
+
+        object.update(camera);
+    }
+
+    /// Registers keyboard, mouse and pointer lock listeners. Clicking the
+    /// canvas requests the pointer lock when `use_pointer_lock` is set. They
+    /// are removed when the returned listeners are dropped.
+    pub fn attach(controls: &Rc<RefCell<FlyControls>>, canvas: &HtmlCanvasElement) -> Result<EventListeners, JsValue> {
+        let window = web_sys::window().expect("No global window object");
+        let document = window.document().expect("Should have a document on window");
+        let mut listeners = EventListeners::new();
+
+        let c = controls.clone();
+        listeners.listen(&window, "keydown", move |event: KeyboardEvent| {
+            let code = event.code();
+            if scrolls_page(&code) {
+                event.prevent_default();
+            }
+            c.borrow_mut().key_down(&code);
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(&window, "keyup", move |event: KeyboardEvent| {
+            c.borrow_mut().key_up(&event.code());
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(&window, "blur", move |_event: web_sys::Event| {
+            c.borrow_mut().reset();
+        })?;
+
+        let c = controls.clone();
+        let canvasc = canvas.clone();
+        listeners.listen(canvas, "mousedown", move |_event: MouseEvent| {
+            let mut controls = c.borrow_mut();
+            controls.mouse_down();
+            if controls.use_pointer_lock && !controls.pointer_locked {
+                canvasc.request_pointer_lock();
+            }
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(&window, "mouseup", move |_event: MouseEvent| {
+            c.borrow_mut().mouse_up();
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(&document, "mousemove", move |event: MouseEvent| {
+            c.borrow_mut().mouse_move(event.movement_x() as f32, event.movement_y() as f32);
+        })?;
+
+        let c = controls.clone();
+        let canvasc = canvas.clone();
+        let documentc = document.clone();
+        listeners.listen(&document, "pointerlockchange", move |_event: web_sys::Event| {
+            let locked = documentc.pointer_lock_element().is_some_and(|e| e == **canvasc);
+            c.borrow_mut().pointer_locked = locked;
+        })?;
+
+        Ok(listeners)
+    }
+}
+
+/// Keys whose default action scrolls the page.
+fn scrolls_page(code: &str) -> bool {
+    matches!(code, "Space" | "ArrowUp" | "ArrowDown" | "ArrowLeft" | "ArrowRight")
+}
+
+fn axis(positive: bool, negative: bool) -> f32 {
+    (positive as i32 - negative as i32) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, point3};
+
+    fn camera() -> Rc<Node<Object3d>> {
+        let camera = Node::new_rc(Object3d::new());
+        camera.value.borrow_mut().update(&camera);
+        camera
+    }
+
+    fn position(camera: &Rc<Node<Object3d>>) -> cgmath::Point3<f32> {
+        camera.value.borrow().transform.world_position()
+    }
+
+    #[test]
+    fn test_keys() {
+        let camera = camera();
+        let mut controls = FlyControls::new();
+
+        controls.key_down("KeyW");
+        controls.update(&camera, 1.0);
+        assert_abs_diff_eq!(position(&camera), point3(0.0, 0.0, -5.0), epsilon = 1e-5);
+
+        controls.key_down("ShiftLeft");
+        controls.key_down("Space");
+        controls.key_up("KeyW");
+        controls.update(&camera, 1.0);
+        assert_abs_diff_eq!(position(&camera), point3(0.0, 15.0, -5.0), epsilon = 1e-5);
+
+        // opposite keys cancel out
+        controls.key_down("KeyQ");
+        controls.update(&camera, 1.0);
+        assert_abs_diff_eq!(position(&camera), point3(0.0, 15.0, -5.0), epsilon = 1e-5);
+
+        controls.reset();
+        controls.key_down("ArrowRight");
+        controls.update(&camera, 1.0);
+        assert_abs_diff_eq!(position(&camera), point3(5.0, 15.0, -5.0), epsilon = 1e-5);
+        assert_eq!(controls.movement_speed, 5.0);
+    }
+
+    #[test]
+    fn test_look() {
+        let camera = camera();
+        let mut controls = FlyControls::new();
+        controls.use_pointer_lock = false;
+
+        // only while dragging without pointer lock
+        controls.mouse_move(100.0, 0.0);
+        controls.update(&camera, 0.0);
+        assert_abs_diff_eq!(camera.value.borrow().transform.quaternion, Quaternion::new(1.0, 0.0, 0.0, 0.0));
+
+        controls.mouse_down();
+        controls.look_speed = PI / 200.0;
+        controls.mouse_move(100.0, 0.0);
+        controls.key_down("KeyW");
+        controls.update(&camera, 1.0);
+        assert_abs_diff_eq!(position(&camera), point3(5.0, 0.0, 0.0), epsilon = 1e-4);
+
+        // pitch stops short of straight up
+        controls.mouse_move(0.0, -1000.0);
+        controls.update(&camera, 0.0);
+        let forward = camera.value.borrow().transform.local_to_world_direction(-Vector3::unit_z());
+        assert_abs_diff_eq!(forward.y.asin(), MAX_PITCH, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_scrolls_page() {
+        assert!(scrolls_page("Space"));
+        assert!(scrolls_page("ArrowUp"));
+        assert!(!scrolls_page("KeyW"));
+    }
+}
@@ -0,0 +1,463 @@
+use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+
+use cgmath::{vec2, vec3, InnerSpace, Point3, Rad, Vector2, Vector3};
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlCanvasElement, MouseEvent, TouchEvent, WheelEvent};
+
+use crate::core::{graph::Node, input::web::EventListeners, object3d::Object3d};
+
+const EPS: f32 = 0.000001;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    None,
+    Rotate,
+    Dolly,
+    Pan,
+    TouchRotate,
+    TouchDollyPan,
+}
+
+/// Orbits a camera node around `target`: left drag rotates, middle drag or
+/// wheel zooms, right drag (or shift + left drag) pans. With touch, one finger
+/// rotates and two fingers pinch and pan.
+pub struct OrbitControls {
+    pub target: Point3<f32>,
+
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub min_polar_angle: Rad<f32>,
+    pub max_polar_angle: Rad<f32>,
+
+    pub enable_damping: bool,
+    pub damping_factor: f32,
+
+    pub enable_rotate: bool,
+    pub enable_zoom: bool,
+    pub enable_pan: bool,
+    pub rotate_speed: f32,
+    pub zoom_speed: f32,
+    pub pan_speed: f32,
+
+    /// Height in pixels of the element receiving events, used to convert
+    /// drags into angles and distances.
+    pub element_height: f32,
+
+    state: State,
+    pointer: Vector2<f32>,
+    touch_distance: f32,
+
+    theta_delta: f32,
+    phi_delta: f32,
+    scale: f32,
+    pan_delta: Vector2<f32>,
+    pan_offset: Vector3<f32>,
+}
+
+impl Default for OrbitControls {
+    fn default() -> OrbitControls {
+        OrbitControls::new()
+    }
+}
+
+impl OrbitControls {
+    pub fn new() -> OrbitControls {
+        OrbitControls {
+            target: Point3::new(0.0, 0.0, 0.0),
+            min_distance: 0.0,
+            max_distance: f32::INFINITY,
+            min_polar_angle: Rad(0.0),
+            max_polar_angle: Rad(PI),
+            enable_damping: false,
+            damping_factor: 0.05,
+            enable_rotate: true,
+            enable_zoom: true,
+            enable_pan: true,
+            rotate_speed: 1.0,
+            zoom_speed: 1.0,
+            pan_speed: 1.0,
+            element_height: 1.0,
+            state: State::None,
+            pointer: vec2(0.0, 0.0),
+            touch_distance: 0.0,
+            theta_delta: 0.0,
+            phi_delta: 0.0,
+            scale: 1.0,
+            pan_delta: vec2(0.0, 0.0),
+            pan_offset: vec3(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn rotate_left(&mut self, angle: Rad<f32>) {
+        self.theta_delta -= angle.0;
+    }
+
+    pub fn rotate_up(&mut self, angle: Rad<f32>) {
+        self.phi_delta -= angle.0;
+    }
+
+    pub fn dolly_in(&mut self, factor: f32) {
+        self.scale *= factor;
+    }
+
+    pub fn dolly_out(&mut self, factor: f32) {
+        self.scale /= factor;
+    }
+
+    /// Pans by a screen space offset in pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.pan_delta += vec2(dx, dy);
+    }
+
+    fn zoom_scale(&self) -> f32 {
+        0.95f32.powf(self.zoom_speed)
+    }
+
+    fn rotate_by_pixels(&mut self, delta: Vector2<f32>) {
+        let height = self.element_height.max(1.0);
+        self.rotate_left(Rad(2.0 * PI * delta.x / height * self.rotate_speed));
+        self.rotate_up(Rad(2.0 * PI * delta.y / height * self.rotate_speed));
+    }
+
+    pub fn pointer_down(&mut self, x: f32, y: f32, button: i16, shift: bool) {
+        self.pointer = vec2(x, y);
+        self.state = match button {
+            0 if shift && self.enable_pan => State::Pan,
+            0 if self.enable_rotate => State::Rotate,
+            1 if self.enable_zoom => State::Dolly,
+            2 if self.enable_pan => State::Pan,
+            _ => State::None,
+        };
+    }
+
+    pub fn pointer_move(&mut self, x: f32, y: f32) {
+        let position = vec2(x, y);
+        let delta = position - self.pointer;
+        self.pointer = position;
+
+        match self.state {
+            State::Rotate => self.rotate_by_pixels(delta),
+            State::Dolly => {
+                if delta.y > 0.0 {
+                    self.dolly_out(self.zoom_scale());
+                } else if delta.y < 0.0 {
+                    self.dolly_in(self.zoom_scale());
+                }
+            }
+            State::Pan => self.pan(delta.x * self.pan_speed, delta.y * self.pan_speed),
+            _ => {}
+        }
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.state = State::None;
+    }
+
+    pub fn wheel(&mut self, delta_y: f32) {
+        if !self.enable_zoom {
+            return;
+        }
+        if delta_y > 0.0 {
+            self.dolly_out(self.zoom_scale());
+        } else if delta_y < 0.0 {
+            self.dolly_in(self.zoom_scale());
+        }
+    }
+
+    pub fn touch_start(&mut self, touches: &[(f32, f32)]) {
+        match touches {
+            [(x, y)] if self.enable_rotate => {
+                self.pointer = vec2(*x, *y);
+                self.state = State::TouchRotate;
+            }
+            [a, b, ..] if self.enable_zoom || self.enable_pan => {
+                self.pointer = touch_center(*a, *b);
+                self.touch_distance = touch_distance(*a, *b);
+                self.state = State::TouchDollyPan;
+            }
+            _ => self.state = State::None,
+        }
+    }
+
+    pub fn touch_move(&mut self, touches: &[(f32, f32)]) {
+        match (self.state, touches) {
+            (State::TouchRotate, [(x, y)]) => {
+                let position = vec2(*x, *y);
+                self.rotate_by_pixels(position - self.pointer);
+                self.pointer = position;
+            }
+            (State::TouchDollyPan, [a, b, ..]) => {
+                let distance = touch_distance(*a, *b);
+                if self.enable_zoom && distance > 0.0 && self.touch_distance > 0.0 {
+                    self.dolly_out((distance / self.touch_distance).powf(self.zoom_speed));
+                }
+                self.touch_distance = distance;
+
+                let center = touch_center(*a, *b);
+                if self.enable_pan {
+                    let delta = center - self.pointer;
+                    self.pan(delta.x * self.pan_speed, delta.y * self.pan_speed);
+                }
+                self.pointer = center;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn touch_end(&mut self, touches: &[(f32, f32)]) {
+        // restart the gesture with the remaining fingers
+        self.touch_start(touches);
+    }
+
+    /// Applies the accumulated input to `camera`, keeping it pointed at the
+    /// target. Call once per frame.
+    pub fn update(&mut self, camera: &Rc<Node<Object3d>>) {
+        let (position, right, up) = {
+            let camera = camera.value.borrow();
+            let m = &camera.transform.matrix_world;
+            (
+                camera.transform.world_position(),
+                m.x.truncate().normalize(),
+                m.y.truncate().normalize(),
+            )
+        };
+
+        let offset = position - self.target;
+        let mut radius = offset.magnitude();
+        let mut theta = offset.x.atan2(offset.z);
+        let mut phi = if radius > 0.0 {
+            (offset.y / radius).clamp(-1.0, 1.0).acos()
+        } else {
+            0.0
+        };
+
+        if self.enable_damping {
+            theta += self.theta_delta * self.damping_factor;
+            phi += self.phi_delta * self.damping_factor;
+        } else {
+            theta += self.theta_delta;
+            phi += self.phi_delta;
+        }
+        phi = phi.max(self.min_polar_angle.0).min(self.max_polar_angle.0).clamp(EPS, PI - EPS);
+
+        // with damping the zoom is spread over the next frames, like rotations
+        let scale = if self.enable_damping {
+            self.scale.powf(self.damping_factor)
+        } else {
+            self.scale
+        };
+        radius = (radius * scale).max(self.min_distance).min(self.max_distance);
+
+        // Pan speed matches the movement of a point on the target plane
+        // under a 45 degrees vertical field of view.
+        if self.pan_delta != vec2(0.0, 0.0) {
+            let distance = radius * (PI / 8.0).tan();
+            let height = self.element_height.max(1.0);
+            self.pan_offset += right * (-2.0 * self.pan_delta.x * distance / height);
+            self.pan_offset += up * (2.0 * self.pan_delta.y * distance / height);
+            self.pan_delta = vec2(0.0, 0.0);
+        }
+
+        if self.enable_damping {
+            self.target += self.pan_offset * self.damping_factor;
+        } else {
+            self.target += self.pan_offset;
+        }
+
+        let offset = vec3(radius * phi.sin() * theta.sin(), radius * phi.cos(), radius * phi.sin() * theta.cos());
+        Object3d::set_world_position(camera, self.target + offset);
+        Object3d::look_at(camera, self.target, Vector3::unit_y());
+
+        if self.enable_damping {
+            self.theta_delta *= 1.0 - self.damping_factor;
+            self.phi_delta *= 1.0 - self.damping_factor;
+            self.pan_offset *= 1.0 - self.damping_factor;
+            self.scale /= scale;
+        } else {
+            self.theta_delta = 0.0;
+            self.phi_delta = 0.0;
+            self.pan_offset = vec3(0.0, 0.0, 0.0);
+            self.scale = 1.0;
+        }
+    }
+
+    /// Registers the DOM listeners feeding `controls` from `canvas`. They
+    /// are removed when the returned listeners are dropped.
+    pub fn attach(controls: &Rc<RefCell<OrbitControls>>, canvas: &HtmlCanvasElement) -> Result<EventListeners, JsValue> {
+        let window = web_sys::window().expect("No global window object");
+        let mut listeners = EventListeners::new();
+
+        let c = controls.clone();
+        let canvasc = canvas.clone();
+        listeners.listen(canvas, "mousedown", move |event: MouseEvent| {
+            let mut controls = c.borrow_mut();
+            controls.element_height = canvasc.client_height() as f32;
+            controls.pointer_down(event.client_x() as f32, event.client_y() as f32, event.button(), event.shift_key());
+        })?;
+
+        // move and up are tracked on the window so drags may leave the canvas
+        let c = controls.clone();
+        listeners.listen(&window, "mousemove", move |event: MouseEvent| {
+            c.borrow_mut().pointer_move(event.client_x() as f32, event.client_y() as f32);
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(&window, "mouseup", move |_event: MouseEvent| {
+            c.borrow_mut().pointer_up();
+        })?;
+
+        let c = controls.clone();
+        listeners.listen(canvas, "wheel", move |event: WheelEvent| {
+            event.prevent_default();
+            c.borrow_mut().wheel(event.delta_y() as f32);
+        })?;
+
+        listeners.listen(canvas, "contextmenu", move |event: MouseEvent| {
+            event.prevent_default();
+        })?;
+
+        for (name, handler) in [
+            ("touchstart", OrbitControls::touch_start as fn(&mut OrbitControls, &[(f32, f32)])),
+            ("touchmove", OrbitControls::touch_move),
+            ("touchend", OrbitControls::touch_end),
+            ("touchcancel", OrbitControls::touch_end),
+        ] {
+            let c = controls.clone();
+            let canvasc = canvas.clone();
+            listeners.listen(canvas, name, move |event: TouchEvent| {
+                event.prevent_default();
+                let mut controls = c.borrow_mut();
+                controls.element_height = canvasc.client_height() as f32;
+                handler(&mut controls, &touch_points(&event));
+            })?;
+        }
+
+        Ok(listeners)
+    }
+}
+
+fn touch_points(event: &TouchEvent) -> Vec<(f32, f32)> {
+    let touches = event.touches();
+    (0..touches.length())
+        .filter_map(|i| touches.get(i))
+        .map(|t| (t.client_x() as f32, t.client_y() as f32))
+        .collect()
+}
+
+fn touch_center(a: (f32, f32), b: (f32, f32)) -> Vector2<f32> {
+    vec2((a.0 + b.0) * 0.5, (a.1 + b.1) * 0.5)
+}
+
+fn touch_distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    vec2(a.0 - b.0, a.1 - b.1).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, point3, EuclideanSpace, MetricSpace};
+
+    fn camera_at(position: Vector3<f32>) -> Rc<Node<Object3d>> {
+        let camera = Node::new_rc(Object3d::new());
+        camera.value.borrow_mut().transform.position = position;
+        camera.value.borrow_mut().update(&camera);
+        camera
+    }
+
+    fn camera_position(camera: &Rc<Node<Object3d>>) -> Point3<f32> {
+        camera.value.borrow().transform.world_position()
+    }
+
+    #[test]
+    fn test_rotate() {
+        let camera = camera_at(vec3(0.0, 0.0, 10.0));
+        let mut controls = OrbitControls::new();
+        controls.element_height = 100.0;
+
+        // a quarter of the height is a quarter turn
+        controls.pointer_down(0.0, 0.0, 0, false);
+        controls.pointer_move(25.0, 0.0);
+        controls.pointer_up();
+        controls.update(&camera);
+        assert_abs_diff_eq!(camera_position(&camera), point3(-10.0, 0.0, 0.0), epsilon = 1e-4);
+
+        // released, moving does nothing
+        controls.pointer_move(50.0, 0.0);
+        controls.update(&camera);
+        assert_abs_diff_eq!(camera_position(&camera), point3(-10.0, 0.0, 0.0), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_polar_limits() {
+        let camera = camera_at(vec3(0.0, 0.0, 10.0));
+        let mut controls = OrbitControls::new();
+        controls.element_height = 100.0;
+        controls.max_polar_angle = Rad(PI / 4.0);
+
+        controls.pointer_down(0.0, 0.0, 0, false);
+        controls.pointer_move(0.0, -50.0);
+        controls.update(&camera);
+        let position = camera_position(&camera);
+        assert_abs_diff_eq!(position.y, 10.0 * (PI / 4.0).cos(), epsilon = 1e-4);
+        assert_abs_diff_eq!(position.distance(Point3::origin()), 10.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_pan() {
+        let camera = camera_at(vec3(0.0, 0.0, 10.0));
+        let mut controls = OrbitControls::new();
+        controls.element_height = 100.0;
+
+        // shift + left drag pans like the right button
+        controls.pointer_down(0.0, 0.0, 0, true);
+        controls.pointer_move(10.0, 0.0);
+        controls.update(&camera);
+
+        let x = -2.0 * 10.0 * 10.0 * (PI / 8.0).tan() / 100.0;
+        assert_abs_diff_eq!(controls.target, point3(x, 0.0, 0.0), epsilon = 1e-4);
+        assert_abs_diff_eq!(camera_position(&camera), point3(x, 0.0, 10.0), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_dolly_limits() {
+        let camera = camera_at(vec3(0.0, 0.0, 10.0));
+        let mut controls = OrbitControls::new();
+        controls.min_distance = 5.0;
+        controls.max_distance = 20.0;
+
+        controls.wheel(-1.0);
+        controls.update(&camera);
+        assert_abs_diff_eq!(camera_position(&camera).z, 9.5, epsilon = 1e-4);
+
+        for _ in 0..100 {
+            controls.wheel(-1.0);
+        }
+        controls.update(&camera);
+        assert_abs_diff_eq!(camera_position(&camera).z, 5.0, epsilon = 1e-4);
+
+        // middle drag down zooms out
+        controls.pointer_down(0.0, 0.0, 1, false);
+        for y in 1..100 {
+            controls.pointer_move(0.0, y as f32);
+        }
+        controls.update(&camera);
+        assert_abs_diff_eq!(camera_position(&camera).z, 20.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn test_damping() {
+        let camera = camera_at(vec3(0.0, 0.0, 10.0));
+        let mut controls = OrbitControls::new();
+        controls.enable_damping = true;
+
+        controls.wheel(-1.0);
+        controls.update(&camera);
+        let z = camera_position(&camera).z;
+        assert!(z < 10.0 && z > 9.5);
+
+        for _ in 0..500 {
+            controls.update(&camera);
+        }
+        assert_abs_diff_eq!(camera_position(&camera).z, 9.5, epsilon = 1e-3);
+    }
+}
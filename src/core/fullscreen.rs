@@ -0,0 +1,50 @@
+use std::cell::RefCell;
+
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext as GL, WebGlVertexArrayObject};
+
+use super::{material::Material, program::ProgramCache, uniform::UniformSet};
+
+/// Vertex shader of full screen triangle materials, passes `vUv` to the
+/// fragment shader.
+pub const FULLSCREEN_VERTEX_SHADER: &str = include_str!("shaders/fullscreen_vertex.glsl");
+
+/// Draws materials over the whole viewport with a single triangle generated
+/// from `gl_VertexID`, for the background and the post-processing passes.
+pub struct FullscreenTriangle {
+    // no attributes, an empty VAO is enough
+    vao: WebGlVertexArrayObject,
+}
+
+impl FullscreenTriangle {
+    pub fn new(gl: &GL) -> Result<FullscreenTriangle, JsValue> {
+        Ok(FullscreenTriangle {
+            vao: gl.create_vertex_array().ok_or("Could not create vertex array object")?,
+        })
+    }
+
+    /// Draws `material` into the bound framebuffer without writing depth.
+    /// `uniforms` are uploaded after those of the material, for values it
+    /// does not store.
+    pub fn draw(
+        &self,
+        gl: &GL,
+        programs: &RefCell<ProgramCache>,
+        max_texture_units: usize,
+        material: &mut Material,
+        uniforms: &UniformSet,
+    ) -> Result<(), JsValue> {
+        let program = material.get_program(gl, &mut programs.borrow_mut())?.program.clone();
+        gl.use_program(Some(&program));
+        material.bind_textures(gl, max_texture_units)?;
+        material.upload_uniforms(gl);
+        material.upload_uniform_set(gl, uniforms);
+
+        gl.bind_vertex_array(Some(&self.vao));
+        gl.depth_mask(false);
+        gl.draw_arrays(GL::TRIANGLES, 0, 3);
+        gl.depth_mask(true);
+        gl.bind_vertex_array(None);
+        Ok(())
+    }
+}
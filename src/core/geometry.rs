@@ -1,42 +1,68 @@
 use wasm_bindgen::JsValue;
-use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlVertexArrayObject};
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlVertexArrayObject};
+use weblog::console_warn;
+
+use super::program::{type_name, ShaderProgram};
+
+/// Locations bound to the geometry attributes before linking any program.
+pub const ATTRIBUTE_LOCATIONS: [(&str, u32); 3] = [("position", 0), ("normal", 1), ("uv", 2)];
+
+pub fn attribute_location(name: &str) -> Option<u32> {
+    ATTRIBUTE_LOCATIONS
+        .iter()
+        .find(|(attribute, _)| *attribute == name)
+        .map(|(_, location)| *location)
+}
 
 pub struct Geometry {
     pub positions: Option<Vec<f32>>,
     pub indexes: Option<Vec<u16>>,
     pub normals: Option<Vec<f32>>,
+    pub uvs: Option<Vec<f32>>,
     pub vao: Option<WebGlVertexArrayObject>,
 }
 
+impl Default for Geometry {
+    fn default() -> Geometry {
+        Geometry::new()
+    }
+}
+
 impl Geometry {
     pub fn new() -> Geometry {
         Geometry {
             positions: None,
             indexes: None,
             normals: None,
+            uvs: None,
             vao: None,
         }
     }
 
-    pub fn set_positions(&mut self, positions: &Vec<f32>) {
+    pub fn set_positions(&mut self, positions: &[f32]) {
         self.positions.replace(positions.to_vec());
     }
-    pub fn set_normals(&mut self, normals: &Vec<f32>) {
+    pub fn set_normals(&mut self, normals: &[f32]) {
         self.normals.replace(normals.to_vec());
     }
-    pub fn set_indexes(&mut self, indexes: &Vec<u16>) {
+    pub fn set_uvs(&mut self, uvs: &[f32]) {
+        self.uvs.replace(uvs.to_vec());
+    }
+    pub fn set_indexes(&mut self, indexes: &[u16]) {
         self.indexes.replace(indexes.to_vec());
     }
 
+    /// Creates the VAO, shared by every program since `link_program` binds
+    /// `ATTRIBUTE_LOCATIONS` before linking: another program, or the same
+    /// sources rebuilt with other defines, reads the same buffers. Active
+    /// attributes of the first `program` without data are reported.
     pub fn get_vao(
         &mut self,
         gl: &WebGl2RenderingContext,
-        program: &WebGlProgram,
+        program: &ShaderProgram,
     ) -> Result<&WebGlVertexArrayObject, JsValue> {
         if self.vao.is_none() {
-            let ibo = create_ibo_vector(&gl, self.indexes.as_ref().unwrap())?;
-            let position_vbo = create_vbo_vector(gl, self.positions.as_ref().unwrap())?;
-            let normal_vbo = create_vbo_vector(gl, self.normals.as_ref().unwrap())?;
+            let ibo = create_ibo_vector(gl, self.indexes.as_ref().unwrap())?;
 
             // setup buffers and attributes to the VAO
             let vao = gl
@@ -46,32 +72,39 @@ impl Geometry {
 
             // bind buffer data
             gl.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&ibo));
-            
+
             // set attribute types
-            let position_attribute_location = gl.get_attrib_location(&program, "position") as u32;
-            gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&position_vbo));
-            gl.enable_vertex_attrib_array(position_attribute_location);
-            gl.vertex_attrib_pointer_with_i32(
-                position_attribute_location,
-                3,
-                WebGl2RenderingContext::FLOAT,
-                false,
-                0,
-                0,
-            );
-            
-            let normal_attribute_location = gl.get_attrib_location(&program, "normal") as u32;
-            gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&normal_vbo));
-            gl.enable_vertex_attrib_array(normal_attribute_location);
-            gl.vertex_attrib_pointer_with_i32(
-                normal_attribute_location,
-                3,
-                WebGl2RenderingContext::FLOAT,
-                false,
-                0,
-                0,
-            );
-            
+            let attributes = [
+                ("position", self.positions.as_ref(), 3),
+                ("normal", self.normals.as_ref(), 3),
+                ("uv", self.uvs.as_ref(), 2),
+            ];
+            for (name, data, size) in attributes.iter() {
+                let (data, location) = match (data, attribute_location(name)) {
+                    (Some(data), Some(location)) => (data, location),
+                    _ => continue,
+                };
+
+                let vbo = create_vbo_vector(gl, data)?;
+                gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_with_i32(location, *size, WebGl2RenderingContext::FLOAT, false, 0, 0);
+            }
+
+            for attribute in program.attributes.iter() {
+                let fed = attributes
+                    .iter()
+                    .any(|(name, data, _)| data.is_some() && attribute.is_named(name));
+                if !fed && !attribute.name.starts_with("gl_") {
+                    let message = format!(
+                        "Geometry has no data for attribute '{}' ({}), it reads a constant value",
+                        attribute.name,
+                        type_name(attribute.kind)
+                    );
+                    console_warn!(message);
+                }
+            }
+
             gl.bind_vertex_array(None);
 
             self.vao = Some(vao);
@@ -83,12 +116,12 @@ impl Geometry {
 
 pub fn create_vbo_vector(
     gl: &WebGl2RenderingContext,
-    data: &Vec<f32>,
+    data: &[f32],
 ) -> Result<WebGlBuffer, String> {
     let vbo = gl.create_buffer().ok_or("Failed to create buffer")?;
     gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
     unsafe {
-        let f32_array = js_sys::Float32Array::view(&(*data));
+        let f32_array = js_sys::Float32Array::view(data);
         gl.buffer_data_with_array_buffer_view(
             WebGl2RenderingContext::ARRAY_BUFFER,
             &f32_array,
@@ -102,7 +135,7 @@ pub fn create_vbo_vector(
 
 pub fn create_ibo_vector(
     gl: &WebGl2RenderingContext,
-    data: &Vec<u16>,
+    data: &[u16],
 ) -> Result<WebGlBuffer, String> {
     let ibo = gl.create_buffer().ok_or("Failed to create buffer")?;
     gl.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&ibo));
@@ -118,3 +151,20 @@ pub fn create_ibo_vector(
 
     Ok(ibo)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_locations() {
+        assert_eq!(attribute_location("position"), Some(0));
+        assert_eq!(attribute_location("uv"), Some(2));
+        assert_eq!(attribute_location("tangent"), None);
+
+        // a shared VAO needs one location per attribute
+        for (i, (_, a)) in ATTRIBUTE_LOCATIONS.iter().enumerate() {
+            assert!(ATTRIBUTE_LOCATIONS[i + 1..].iter().all(|(_, b)| a != b));
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Development builds only: replaces shader sources while the app runs.
+//!
+//! Shaders can be edited live in two ways:
+//! - as chunks included by the materials, e.g. a fragment shader
+//!   `#include "water"`, pushed from the browser console with
+//!   `reloadShaderChunk(name, source)`;
+//! - as the vertex or fragment source of the materials with a `name`, pushed
+//!   with `reloadMaterialShader(name, "vertex" | "fragment", source)`.
+//!
+//! Both can also be polled from a dev server with `ShaderWatcher`, or from the
+//! console with `watchShaderChunk(name, url, intervalMs)` and
+//! `watchMaterialShader(name, stage, url, intervalMs)` until
+//! `unwatchShaders()`. The new sources are applied by the renderer at the
+//! start of the next frame.
+
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::XmlHttpRequest;
+use weblog::console_warn;
+
+use super::{material::Material, shader_error::ShaderStage};
+
+/// A new shader stage source for the materials named `material`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MaterialReload {
+    pub material: String,
+    pub stage: ShaderStage,
+    pub source: String,
+}
+
+impl MaterialReload {
+    /// Replaces the stage source of `material` if it has the target name.
+    pub fn apply(&self, material: &mut Material) -> bool {
+        if material.name.as_deref() != Some(self.material.as_str()) {
+            return false;
+        }
+        match self.stage {
+            ShaderStage::Vertex => {
+                let fragment_shader = material.fragment_shader().to_string();
+                material.set_shaders(self.source.as_str(), fragment_shader);
+            }
+            ShaderStage::Fragment => {
+                let vertex_shader = material.vertex_shader().to_string();
+                material.set_shaders(vertex_shader, self.source.as_str());
+            }
+        }
+        true
+    }
+}
+
+thread_local! {
+    static PENDING: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    static PENDING_MATERIALS: RefCell<Vec<MaterialReload>> = const { RefCell::new(Vec::new()) };
+    static WATCHERS: RefCell<Vec<ShaderWatcher>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Queues a new source for the chunk `name`.
+#[wasm_bindgen(js_name = reloadShaderChunk)]
+pub fn reload_shader_chunk(name: &str, source: &str) {
+    PENDING.with(|pending| pending.borrow_mut().push((name.to_string(), source.to_string())));
+}
+
+/// Queues a new `stage` source, "vertex" or "fragment", for the materials
+/// named `name`.
+#[wasm_bindgen(js_name = reloadMaterialShader)]
+pub fn reload_material_shader(name: &str, stage: &str, source: &str) -> Result<(), JsValue> {
+    let stage = parse_stage(stage)?;
+    queue_material_reload(name, stage, source);
+    Ok(())
+}
+
+fn queue_material_reload(name: &str, stage: ShaderStage, source: &str) {
+    PENDING_MATERIALS.with(|pending| {
+        pending.borrow_mut().push(MaterialReload {
+            material: name.to_string(),
+            stage,
+            source: source.to_string(),
+        })
+    });
+}
+
+/// Polls `url` for the chunk `name` until `unwatchShaders` is called.
+#[wasm_bindgen(js_name = watchShaderChunk)]
+pub fn watch_shader_chunk(name: &str, url: &str, interval_ms: i32) -> Result<(), JsValue> {
+    let watcher = ShaderWatcher::watch(name, url, interval_ms)?;
+    WATCHERS.with(|watchers| watchers.borrow_mut().push(watcher));
+    Ok(())
+}
+
+/// Polls `url` for the `stage` source, "vertex" or "fragment", of the
+/// materials named `name` until `unwatchShaders` is called.
+#[wasm_bindgen(js_name = watchMaterialShader)]
+pub fn watch_material_shader(name: &str, stage: &str, url: &str, interval_ms: i32) -> Result<(), JsValue> {
+    let watcher = ShaderWatcher::watch_material(name, parse_stage(stage)?, url, interval_ms)?;
+    WATCHERS.with(|watchers| watchers.borrow_mut().push(watcher));
+    Ok(())
+}
+
+/// Stops the watchers started from the console.
+#[wasm_bindgen(js_name = unwatchShaders)]
+pub fn unwatch_shaders() {
+    WATCHERS.with(|watchers| watchers.borrow_mut().clear());
+}
+
+fn parse_stage(stage: &str) -> Result<ShaderStage, String> {
+    match stage {
+        "vertex" => Ok(ShaderStage::Vertex),
+        "fragment" => Ok(ShaderStage::Fragment),
+        _ => Err(format!("Unknown shader stage '{}', expected 'vertex' or 'fragment'", stage)),
+    }
+}
+
+/// The chunks queued since the last call, in order.
+pub fn take_pending() -> Vec<(String, String)> {
+    PENDING.with(|pending| pending.borrow_mut().drain(..).collect())
+}
+
+/// The material sources queued since the last call, in order.
+pub fn take_pending_materials() -> Vec<MaterialReload> {
+    PENDING_MATERIALS.with(|pending| pending.borrow_mut().drain(..).collect())
+}
+
+/// What a watcher reloads.
+#[derive(Clone)]
+enum Target {
+    Chunk(String),
+    Material(String, ShaderStage),
+}
+
+impl Target {
+    fn reload(&self, source: &str) {
+        match self {
+            Target::Chunk(name) => reload_shader_chunk(name, source),
+            Target::Material(name, stage) => queue_material_reload(name, *stage, source),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Chunk(name) => write!(f, "shader chunk '{}'", name),
+            Target::Material(name, stage) => write!(f, "{} shader of material '{}'", stage, name),
+        }
+    }
+}
+
+/// Polls `url` and queues the chunk or material source each time the served
+/// source changes.
+/// Polling stops when the watcher is dropped.
+pub struct ShaderWatcher {
+    interval: i32,
+    _poll: Closure<dyn FnMut()>,
+}
+
+impl ShaderWatcher {
+    pub fn watch(name: &str, url: &str, interval_ms: i32) -> Result<ShaderWatcher, JsValue> {
+        ShaderWatcher::watch_target(Target::Chunk(name.to_string()), url, interval_ms)
+    }
+
+    /// Polls `url` for the `stage` source of the materials named `name`.
+    pub fn watch_material(name: &str, stage: ShaderStage, url: &str, interval_ms: i32) -> Result<ShaderWatcher, JsValue> {
+        ShaderWatcher::watch_target(Target::Material(name.to_string(), stage), url, interval_ms)
+    }
+
+    fn watch_target(target: Target, url: &str, interval_ms: i32) -> Result<ShaderWatcher, JsValue> {
+        let url = url.to_string();
+        let last = Rc::new(RefCell::new(None::<String>));
+
+        let poll = Closure::wrap(Box::new(move || {
+            if let Err(e) = request(&target, &url, last.clone()) {
+                console_warn!(e);
+            }
+        }) as Box<dyn FnMut()>);
+
+        let interval = web_sys::window()
+            .unwrap()
+            .set_interval_with_callback_and_timeout_and_arguments_0(poll.as_ref().unchecked_ref(), interval_ms)?;
+
+        Ok(ShaderWatcher { interval, _poll: poll })
+    }
+}
+
+impl Drop for ShaderWatcher {
+    fn drop(&mut self) {
+        web_sys::window().unwrap().clear_interval_with_handle(self.interval);
+    }
+}
+
+fn request(target: &Target, url: &str, last: Rc<RefCell<Option<String>>>) -> Result<(), JsValue> {
+    let xhr = XmlHttpRequest::new()?;
+    xhr.open("GET", url)?;
+
+    // loadend fires once whatever the outcome, which releases the closure
+    let target = target.clone();
+    let url = url.to_string();
+    let response = xhr.clone();
+    let on_loadend = Closure::once_into_js(move || {
+        let source = match (response.status(), response.response_text()) {
+            (Ok(200), Ok(Some(source))) => source,
+            _ => {
+                let message = format!("Could not fetch the {} from {}", target, url);
+                console_warn!(message);
+                return;
+            }
+        };
+        if last.borrow().as_ref() != Some(&source) {
+            // the first response is the source the app started with
+            if last.borrow().is_some() {
+                target.reload(&source);
+            }
+            last.replace(Some(source));
+        }
+    });
+    xhr.set_onloadend(Some(on_loadend.unchecked_ref()));
+    xhr.send()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stage() {
+        assert_eq!(parse_stage("vertex"), Ok(ShaderStage::Vertex));
+        assert_eq!(parse_stage("fragment"), Ok(ShaderStage::Fragment));
+        assert!(parse_stage("geometry").is_err());
+    }
+
+    #[test]
+    fn test_material_reload() {
+        let mut material = Material::with_shaders("vertex", "fragment");
+        let reload = MaterialReload {
+            material: "water".to_string(),
+            stage: ShaderStage::Fragment,
+            source: "new fragment".to_string(),
+        };
+        assert!(!reload.apply(&mut material));
+        assert_eq!(material.fragment_shader(), "fragment");
+
+        material.name = Some("water".to_string());
+        assert!(reload.apply(&mut material));
+        assert_eq!(material.vertex_shader(), "vertex");
+        assert_eq!(material.fragment_shader(), "new fragment");
+    }
+
+    #[test]
+    fn test_pending_materials() {
+        queue_material_reload("water", ShaderStage::Vertex, "source");
+        let pending = take_pending_materials();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].stage, ShaderStage::Vertex);
+        assert!(take_pending_materials().is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::{vec2, Vector2};
+
+pub mod web;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Other(i16),
+}
+
+impl MouseButton {
+    /// Maps a DOM `MouseEvent.button` value.
+    pub fn from_dom(button: i16) -> MouseButton {
+        match button {
+            0 => MouseButton::Left,
+            1 => MouseButton::Middle,
+            2 => MouseButton::Right,
+            b => MouseButton::Other(b),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TouchPoint {
+    pub id: i32,
+    pub position: Vector2<f32>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct GamepadState {
+    pub connected: bool,
+    pub axes: Vec<f32>,
+    pub buttons: Vec<f32>,
+}
+
+impl GamepadState {
+    pub fn axis(&self, index: usize) -> f32 {
+        self.axes.get(index).copied().unwrap_or(0.0)
+    }
+
+    pub fn button(&self, index: usize) -> f32 {
+        self.buttons.get(index).copied().unwrap_or(0.0)
+    }
+}
+
+/// Raw input as received from the browser. Positions are in pixels relative
+/// to the canvas top left corner.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent {
+    /// `code` is a `KeyboardEvent.code` value such as `"KeyW"`.
+    KeyDown(String),
+    KeyUp(String),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    MouseMove { position: Vector2<f32>, movement: Vector2<f32> },
+    Wheel(Vector2<f32>),
+    /// The full list of active touches after a touch event.
+    Touches(Vec<TouchPoint>),
+    Gamepad { index: usize, state: GamepadState },
+    /// The page lost focus, every key and button is released.
+    Blur,
+}
+
+/// Per frame input state. Events are buffered with `push` as they arrive and
+/// applied by `update`, which must be called once at the start of each frame
+/// before any query.
+pub struct Input {
+    queue: Vec<InputEvent>,
+
+    keys_down: HashSet<String>,
+    keys_pressed: HashSet<String>,
+    keys_released: HashSet<String>,
+
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+
+    pointer_position: Vector2<f32>,
+    pointer_delta: Vector2<f32>,
+    wheel: Vector2<f32>,
+
+    touches: Vec<TouchPoint>,
+    previous_touches: Vec<TouchPoint>,
+
+    gamepads: HashMap<usize, GamepadState>,
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
+    }
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            queue: Vec::new(),
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            pointer_position: vec2(0.0, 0.0),
+            pointer_delta: vec2(0.0, 0.0),
+            wheel: vec2(0.0, 0.0),
+            touches: Vec::new(),
+            previous_touches: Vec::new(),
+            gamepads: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        self.queue.push(event);
+    }
+
+    /// Starts a new frame: clears the per frame state and applies the
+    /// buffered events.
+    pub fn update(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.pointer_delta = vec2(0.0, 0.0);
+        self.wheel = vec2(0.0, 0.0);
+        self.previous_touches = self.touches.clone();
+
+        let queue = std::mem::take(&mut self.queue);
+        for event in queue {
+            self.apply(event);
+        }
+    }
+
+    fn apply(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::KeyDown(code) => {
+                // ignore auto repeat
+                if self.keys_down.insert(code.clone()) {
+                    self.keys_pressed.insert(code);
+                }
+            }
+            InputEvent::KeyUp(code) => {
+                if self.keys_down.remove(&code) {
+                    self.keys_released.insert(code);
+                }
+            }
+            InputEvent::MouseDown(button) => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            InputEvent::MouseUp(button) => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+            InputEvent::MouseMove { position, movement } => {
+                self.pointer_position = position;
+                self.pointer_delta += movement;
+            }
+            InputEvent::Wheel(delta) => self.wheel += delta,
+            InputEvent::Touches(touches) => self.touches = touches,
+            InputEvent::Gamepad { index, state } => {
+                self.gamepads.insert(index, state);
+            }
+            InputEvent::Blur => {
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+                self.touches.clear();
+            }
+        }
+    }
+
+    pub fn key_down(&self, code: &str) -> bool {
+        self.keys_down.contains(code)
+    }
+
+    /// True only on the frame the key went down.
+    pub fn key_pressed(&self, code: &str) -> bool {
+        self.keys_pressed.contains(code)
+    }
+
+    /// True only on the frame the key went up.
+    pub fn key_released(&self, code: &str) -> bool {
+        self.keys_released.contains(code)
+    }
+
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn pointer_position(&self) -> Vector2<f32> {
+        self.pointer_position
+    }
+
+    /// Pointer movement accumulated over the frame. Unlike the difference of
+    /// positions this keeps working while the pointer is locked.
+    pub fn pointer_delta(&self) -> Vector2<f32> {
+        self.pointer_delta
+    }
+
+    pub fn wheel_delta(&self) -> Vector2<f32> {
+        self.wheel
+    }
+
+    pub fn touches(&self) -> &[TouchPoint] {
+        &self.touches
+    }
+
+    /// Movement of the touch `id` since the previous frame.
+    pub fn touch_delta(&self, id: i32) -> Vector2<f32> {
+        let current = self.touches.iter().find(|t| t.id == id);
+        let previous = self.previous_touches.iter().find(|t| t.id == id);
+        match (current, previous) {
+            (Some(c), Some(p)) => c.position - p.position,
+            _ => vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn gamepad(&self, index: usize) -> Option<&GamepadState> {
+        self.gamepads.get(&index).filter(|g| g.connected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        let mut input = Input::new();
+        input.push(InputEvent::KeyDown("KeyW".to_string()));
+        input.update();
+        assert!(input.key_down("KeyW"));
+        assert!(input.key_pressed("KeyW"));
+
+        // auto repeat does not press again
+        input.push(InputEvent::KeyDown("KeyW".to_string()));
+        input.update();
+        assert!(input.key_down("KeyW"));
+        assert!(!input.key_pressed("KeyW"));
+
+        input.push(InputEvent::KeyUp("KeyW".to_string()));
+        input.update();
+        assert!(!input.key_down("KeyW"));
+        assert!(input.key_released("KeyW"));
+
+        input.update();
+        assert!(!input.key_released("KeyW"));
+    }
+
+    #[test]
+    fn test_pointer_and_blur() {
+        let mut input = Input::new();
+        input.push(InputEvent::MouseDown(MouseButton::Left));
+        input.push(InputEvent::MouseMove { position: vec2(10.0, 5.0), movement: vec2(2.0, 1.0) });
+        input.push(InputEvent::MouseMove { position: vec2(12.0, 6.0), movement: vec2(2.0, 1.0) });
+        input.push(InputEvent::Wheel(vec2(0.0, 100.0)));
+        input.update();
+        assert!(input.button_pressed(MouseButton::Left));
+        assert_eq!(input.pointer_position(), vec2(12.0, 6.0));
+        assert_eq!(input.pointer_delta(), vec2(4.0, 2.0));
+        assert_eq!(input.wheel_delta(), vec2(0.0, 100.0));
+
+        input.push(InputEvent::Blur);
+        input.update();
+        assert!(!input.button_down(MouseButton::Left));
+        assert!(input.button_released(MouseButton::Left));
+        assert_eq!(input.pointer_delta(), vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_touches() {
+        let mut input = Input::new();
+        input.push(InputEvent::Touches(vec![TouchPoint { id: 3, position: vec2(1.0, 1.0) }]));
+        input.update();
+        input.push(InputEvent::Touches(vec![TouchPoint { id: 3, position: vec2(4.0, 5.0) }]));
+        input.update();
+        assert_eq!(input.touches().len(), 1);
+        assert_eq!(input.touch_delta(3), vec2(3.0, 4.0));
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::vec2;
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{EventTarget, Gamepad, GamepadButton, HtmlCanvasElement, KeyboardEvent, MouseEvent, TouchEvent, WheelEvent};
+
+use super::{GamepadState, Input, InputEvent, MouseButton, TouchPoint};
+
+type Listener = Closure<dyn FnMut(web_sys::Event)>;
+
+/// DOM event listeners, removed when this is dropped.
+#[derive(Default)]
+pub struct EventListeners {
+    listeners: Vec<(EventTarget, &'static str, Listener)>,
+}
+
+impl EventListeners {
+    pub fn new() -> EventListeners {
+        EventListeners { listeners: Vec::new() }
+    }
+
+    pub fn listen<E, F>(&mut self, target: &EventTarget, name: &'static str, mut f: F) -> Result<(), JsValue>
+    where
+        E: JsCast,
+        F: FnMut(E) + 'static,
+    {
+        let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            f(event.unchecked_into::<E>());
+        }) as Box<dyn FnMut(_)>);
+        target.add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())?;
+        self.listeners.push((target.clone(), name, closure));
+        Ok(())
+    }
+}
+
+impl Drop for EventListeners {
+    fn drop(&mut self) {
+        for (target, name, closure) in &self.listeners {
+            let _ = target.remove_event_listener_with_callback(name, closure.as_ref().unchecked_ref());
+        }
+    }
+}
+
+/// DOM listeners feeding an `Input`. The listeners are removed when this is
+/// dropped.
+pub struct InputListeners {
+    _listeners: EventListeners,
+}
+
+impl InputListeners {
+    /// Pointer, wheel and touch events are read from `canvas`, keyboard and
+    /// focus events from the window.
+    pub fn attach(input: &Rc<RefCell<Input>>, canvas: &HtmlCanvasElement) -> Result<InputListeners, JsValue> {
+        let window = web_sys::window().expect("No global window object");
+        let mut listeners = EventListeners::new();
+
+        let i = input.clone();
+        listeners.listen(&window, "keydown", move |event: KeyboardEvent| {
+            i.borrow_mut().push(InputEvent::KeyDown(event.code()));
+        })?;
+        let i = input.clone();
+        listeners.listen(&window, "keyup", move |event: KeyboardEvent| {
+            i.borrow_mut().push(InputEvent::KeyUp(event.code()));
+        })?;
+        let i = input.clone();
+        listeners.listen(&window, "blur", move |_event: web_sys::Event| {
+            i.borrow_mut().push(InputEvent::Blur);
+        })?;
+
+        let i = input.clone();
+        listeners.listen(canvas, "mousedown", move |event: MouseEvent| {
+            i.borrow_mut().push(InputEvent::MouseDown(MouseButton::from_dom(event.button())));
+        })?;
+        // released outside of the canvas still counts
+        let i = input.clone();
+        listeners.listen(&window, "mouseup", move |event: MouseEvent| {
+            i.borrow_mut().push(InputEvent::MouseUp(MouseButton::from_dom(event.button())));
+        })?;
+        let i = input.clone();
+        listeners.listen(canvas, "mousemove", move |event: MouseEvent| {
+            i.borrow_mut().push(InputEvent::MouseMove {
+                position: vec2(event.offset_x() as f32, event.offset_y() as f32),
+                movement: vec2(event.movement_x() as f32, event.movement_y() as f32),
+            });
+        })?;
+        let i = input.clone();
+        listeners.listen(canvas, "wheel", move |event: WheelEvent| {
+            event.prevent_default();
+            i.borrow_mut().push(InputEvent::Wheel(vec2(event.delta_x() as f32, event.delta_y() as f32)));
+        })?;
+        listeners.listen(canvas, "contextmenu", move |event: MouseEvent| {
+            event.prevent_default();
+        })?;
+
+        for name in ["touchstart", "touchmove", "touchend", "touchcancel"] {
+            let i = input.clone();
+            let canvasc = canvas.clone();
+            listeners.listen(canvas, name, move |event: TouchEvent| {
+                event.prevent_default();
+                let rect = canvasc.get_bounding_client_rect();
+                let touches = event.touches();
+                let points = (0..touches.length())
+                    .filter_map(|n| touches.get(n))
+                    .map(|t| TouchPoint {
+                        id: t.identifier(),
+                        position: vec2(
+                            (t.client_x() as f64 - rect.left()) as f32,
+                            (t.client_y() as f64 - rect.top()) as f32,
+                        ),
+                    })
+                    .collect();
+                i.borrow_mut().push(InputEvent::Touches(points));
+            })?;
+        }
+
+        Ok(InputListeners { _listeners: listeners })
+    }
+}
+
+/// Gamepads have no events for axes and buttons, their state is polled once
+/// per frame before `Input::update`.
+pub fn poll_gamepads(input: &mut Input) {
+    let navigator = web_sys::window().expect("No global window object").navigator();
+    let gamepads = match navigator.get_gamepads() {
+        Ok(gamepads) => gamepads,
+        Err(_) => return,
+    };
+
+    for index in 0..gamepads.length() {
+        let state = match gamepads.get(index).dyn_into::<Gamepad>() {
+            Ok(gamepad) => GamepadState {
+                connected: gamepad.connected(),
+                axes: gamepad.axes().iter().map(|a| a.as_f64().unwrap_or(0.0) as f32).collect(),
+                buttons: gamepad
+                    .buttons()
+                    .iter()
+                    .map(|b| b.dyn_into::<GamepadButton>().map_or(0.0, |b| b.value() as f32))
+                    .collect(),
+            },
+            Err(_) => GamepadState::default(),
+        };
+        input.push(InputEvent::Gamepad { index: index as usize, state });
+    }
+}
@@ -0,0 +1,273 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{vec2, vec3, EuclideanSpace, InnerSpace, Rad, Vector2, Vector3};
+
+use super::{
+    graph::Node,
+    object3d::{Object3d, Transform},
+    shadow::LightShadow,
+    uniform::UniformSet,
+};
+
+// keep in sync with shaders/chunks/lighting.glsl
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 8;
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LightKind {
+    /// Lights every surface evenly, the node transform is ignored.
+    Ambient,
+    /// Shines along the node's -Z axis from infinitely far away.
+    Directional,
+    /// Shines from the node's position in every direction. Light fades to
+    /// zero at `range`, with `decay` shaping the falloff; a `range` of 0
+    /// never fades.
+    Point { range: f32, decay: f32 },
+    /// Shines from the node's position along its -Z axis, within `angle` of
+    /// that axis. `penumbra` is the fraction of the cone fading out.
+    Spot {
+        range: f32,
+        decay: f32,
+        angle: Rad<f32>,
+        penumbra: f32,
+    },
+}
+
+/// A light component, lights move with the node holding them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear RGB.
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Casts shadows when set, for directional and spot lights.
+    pub shadow: Option<LightShadow>,
+}
+
+impl Light {
+    pub fn new(kind: LightKind, color: Vector3<f32>, intensity: f32) -> Light {
+        Light {
+            kind,
+            color,
+            intensity,
+            shadow: None,
+        }
+    }
+
+    pub fn ambient(color: Vector3<f32>, intensity: f32) -> Light {
+        Light::new(LightKind::Ambient, color, intensity)
+    }
+
+    pub fn directional(color: Vector3<f32>, intensity: f32) -> Light {
+        Light::new(LightKind::Directional, color, intensity)
+    }
+
+    pub fn point(color: Vector3<f32>, intensity: f32, range: f32, decay: f32) -> Light {
+        Light::new(LightKind::Point { range, decay }, color, intensity)
+    }
+
+    pub fn spot(color: Vector3<f32>, intensity: f32, range: f32, decay: f32, angle: Rad<f32>, penumbra: f32) -> Light {
+        Light::new(
+            LightKind::Spot {
+                range,
+                decay,
+                angle,
+                penumbra,
+            },
+            color,
+            intensity,
+        )
+    }
+}
+
+/// The lights of a scene in world space, gathered once per frame and
+/// uploaded to every material. Lights past the `MAX_*` limits are ignored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SceneLights {
+    pub ambient: Vector3<f32>,
+    /// Directions towards the lights.
+    pub directional_directions: Vec<Vector3<f32>>,
+    pub directional_colors: Vec<Vector3<f32>>,
+    pub point_positions: Vec<Vector3<f32>>,
+    pub point_colors: Vec<Vector3<f32>>,
+    /// Range and decay.
+    pub point_falloffs: Vec<Vector2<f32>>,
+    pub spot_positions: Vec<Vector3<f32>>,
+    /// Directions the spots shine towards.
+    pub spot_directions: Vec<Vector3<f32>>,
+    pub spot_colors: Vec<Vector3<f32>>,
+    /// Range and decay.
+    pub spot_falloffs: Vec<Vector2<f32>>,
+    /// Cosines of the outer and inner cone angles.
+    pub spot_cones: Vec<Vector2<f32>>,
+    pub directional_shadows: Vec<Option<LightShadow>>,
+    pub spot_shadows: Vec<Option<LightShadow>>,
+}
+
+impl Default for SceneLights {
+    fn default() -> SceneLights {
+        SceneLights::new()
+    }
+}
+
+impl SceneLights {
+    pub fn new() -> SceneLights {
+        SceneLights {
+            ambient: vec3(0.0, 0.0, 0.0),
+            directional_directions: Vec::new(),
+            directional_colors: Vec::new(),
+            point_positions: Vec::new(),
+            point_colors: Vec::new(),
+            point_falloffs: Vec::new(),
+            spot_positions: Vec::new(),
+            spot_directions: Vec::new(),
+            spot_colors: Vec::new(),
+            spot_falloffs: Vec::new(),
+            spot_cones: Vec::new(),
+            directional_shadows: Vec::new(),
+            spot_shadows: Vec::new(),
+        }
+    }
+
+    /// Gathers the lights below `root`, whose world matrices must be up to date.
+    pub fn collect(root: &Rc<Node<Object3d>>) -> SceneLights {
+        let lights = RefCell::new(SceneLights::new());
+        Node::traverse(root, &|node: &Rc<Node<Object3d>>| {
+            let n = node.value.borrow();
+            if let Some(light) = &n.light {
+                lights.borrow_mut().add(light, &n.transform);
+            }
+        });
+        lights.into_inner()
+    }
+
+    pub fn add(&mut self, light: &Light, transform: &Transform) {
+        let color = light.color * light.intensity;
+        let position = transform.world_position().to_vec();
+        let forward = transform.local_to_world_direction(vec3(0.0, 0.0, -1.0)).normalize();
+
+        match light.kind {
+            LightKind::Ambient => self.ambient += color,
+            LightKind::Directional => {
+                if self.directional_colors.len() < MAX_DIRECTIONAL_LIGHTS {
+                    self.directional_directions.push(-forward);
+                    self.directional_colors.push(color);
+                    self.directional_shadows.push(light.shadow);
+                }
+            }
+            LightKind::Point { range, decay } => {
+                if self.point_colors.len() < MAX_POINT_LIGHTS {
+                    self.point_positions.push(position);
+                    self.point_colors.push(color);
+                    self.point_falloffs.push(vec2(range, decay));
+                }
+            }
+            LightKind::Spot {
+                range,
+                decay,
+                angle,
+                penumbra,
+            } => {
+                if self.spot_colors.len() < MAX_SPOT_LIGHTS {
+                    let inner = angle * (1.0 - penumbra.clamp(0.0, 1.0));
+                    self.spot_positions.push(position);
+                    self.spot_directions.push(forward);
+                    self.spot_colors.push(color);
+                    self.spot_falloffs.push(vec2(range, decay));
+                    self.spot_cones.push(vec2(angle.0.cos(), inner.0.cos()));
+                    self.spot_shadows.push(light.shadow);
+                }
+            }
+        }
+    }
+
+    /// The values of the uniforms of the `lighting` chunk, built once per
+    /// frame and uploaded to every material.
+    pub fn uniforms(&self) -> UniformSet {
+        let mut uniforms = UniformSet::new();
+        uniforms.set("ambientLightColor", self.ambient);
+
+        let count = |len: usize| len as i32;
+        uniforms.set("numDirectionalLights", count(self.directional_colors.len()));
+        uniforms.set("numPointLights", count(self.point_colors.len()));
+        uniforms.set("numSpotLights", count(self.spot_colors.len()));
+
+        // empty arrays are not valid uniform values, the shader skips them anyway
+        for (name, values) in [
+            ("directionalLightDirections", &self.directional_directions),
+            ("directionalLightColors", &self.directional_colors),
+            ("pointLightPositions", &self.point_positions),
+            ("pointLightColors", &self.point_colors),
+            ("spotLightPositions", &self.spot_positions),
+            ("spotLightDirections", &self.spot_directions),
+            ("spotLightColors", &self.spot_colors),
+        ] {
+            if !values.is_empty() {
+                uniforms.set(name, values.clone());
+            }
+        }
+        for (name, values) in [
+            ("pointLightFalloffs", &self.point_falloffs),
+            ("spotLightFalloffs", &self.spot_falloffs),
+            ("spotLightCones", &self.spot_cones),
+        ] {
+            if !values.is_empty() {
+                uniforms.set(name, values.clone());
+            }
+        }
+        uniforms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uniform::Uniform;
+    use cgmath::{assert_abs_diff_eq, Deg, Quaternion, Rotation3};
+
+    #[test]
+    fn test_collect() {
+        let root = Node::new_rc(Object3d::new());
+        let arm = Node::new_rc(Object3d::new());
+        let spot = Node::new_rc(Object3d::new());
+        Node::add_child(&root, &arm);
+        Node::add_child(&arm, &spot);
+
+        root.value.borrow_mut().light = Some(Light::ambient(vec3(1.0, 1.0, 1.0), 0.25));
+        arm.value.borrow_mut().transform.position = vec3(0.0, 2.0, 0.0);
+        arm.value.borrow_mut().transform.quaternion = Quaternion::from_angle_x(Deg(-90.0));
+        arm.value.borrow_mut().light = Some(Light::directional(vec3(1.0, 0.5, 0.0), 2.0));
+        spot.value.borrow_mut().transform.position = vec3(1.0, 0.0, 0.0);
+        spot.value.borrow_mut().light = Some(Light::spot(vec3(1.0, 1.0, 1.0), 1.0, 10.0, 2.0, Deg(60.0).into(), 0.5));
+        Node::traverse(&root, &|node: &Rc<Node<Object3d>>| {
+            node.value.borrow_mut().update(node);
+        });
+
+        let lights = SceneLights::collect(&root);
+        assert_abs_diff_eq!(lights.ambient, vec3(0.25, 0.25, 0.25));
+
+        // the arm faces down, the directional light comes from above
+        assert_eq!(lights.directional_colors, vec![vec3(2.0, 1.0, 0.0)]);
+        assert_abs_diff_eq!(lights.directional_directions[0], vec3(0.0, 1.0, 0.0), epsilon = 1e-6);
+
+        assert_abs_diff_eq!(lights.spot_positions[0], vec3(1.0, 2.0, 0.0), epsilon = 1e-6);
+        assert_abs_diff_eq!(lights.spot_directions[0], vec3(0.0, -1.0, 0.0), epsilon = 1e-6);
+        assert_abs_diff_eq!(lights.spot_cones[0], vec2(0.5, Rad::from(Deg(30.0f32)).0.cos()), epsilon = 1e-6);
+        assert!(lights.point_colors.is_empty());
+    }
+
+    #[test]
+    fn test_uniforms() {
+        let mut lights = SceneLights::new();
+        lights.add(&Light::point(vec3(1.0, 1.0, 1.0), 2.0, 5.0, 1.0), &Transform::new());
+
+        let uniforms = lights.uniforms();
+        assert_eq!(uniforms.get("numPointLights"), Some(&Uniform::Int(1)));
+        assert_eq!(uniforms.get("numSpotLights"), Some(&Uniform::Int(0)));
+        assert_eq!(uniforms.get("pointLightColors"), Some(&Uniform::Vec3Array(vec![vec3(2.0, 2.0, 2.0)])));
+        assert_eq!(uniforms.get("pointLightFalloffs"), Some(&Uniform::Vec2Array(vec![vec2(5.0, 1.0)])));
+        // no empty arrays
+        assert!(uniforms.get("spotLightColors").is_none());
+    }
+}
@@ -1,57 +1,295 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    rc::Rc,
+};
+
 use wasm_bindgen::JsValue;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader, WebGlUniformLocation};
+use weblog::{console_error, console_warn};
+
+use super::{
+    geometry::ATTRIBUTE_LOCATIONS,
+    preprocessor::Preprocessed,
+    program::{ProgramCache, ProgramKey, ShaderProgram},
+    render_state::RenderState,
+    shader_error::{ShaderError, ShaderStage},
+    texture::Texture,
+    uniform::{Uniform, UniformSet},
+};
 
-// pub let Empty : &str = "";
+/// How the fragments of a material combine with the color already drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    Opaque,
+    /// Mixes by the fragment alpha.
+    AlphaBlend,
+    /// Adds the color weighted by its alpha, for glows and particles.
+    Additive,
+    /// Like `AlphaBlend` for colors already multiplied by their alpha.
+    Premultiplied,
+    /// Opaque, but fragments with an alpha below the cutoff are discarded.
+    /// The shader reads the cutoff from the `alphaTest` uniform when
+    /// `ALPHA_TEST` is defined.
+    AlphaTest(f32),
+}
 
-static EMPTY: &'static str = "";
+impl BlendMode {
+    /// Blended materials are drawn after the opaque ones, back to front.
+    pub fn is_transparent(self) -> bool {
+        matches!(self, BlendMode::AlphaBlend | BlendMode::Additive | BlendMode::Premultiplied)
+    }
+}
 
-pub struct Material<'a> {
-    pub vertex_shader: &'a str,
-    pub fragment_shader: &'a str,
+/// Shader sources are owned so materials can be built from sources loaded or
+/// generated at runtime; a changed source is picked up on the next draw.
+pub struct Material {
+    /// Identifies the material for shader reloads in development builds.
+    pub name: Option<String>,
+    pub blend_mode: BlendMode,
+    pub render_state: RenderState,
+    /// The texture whose alpha shadow depth passes test against the
+    /// `BlendMode::AlphaTest` cutoff. Without it the whole mesh casts shadows.
+    pub alpha_map: Option<String>,
 
-    program: Option<WebGlProgram>,
+    vertex_shader: String,
+    fragment_shader: String,
+    defines: BTreeMap<String, String>,
+    // the sources or defines changed since the program was requested
+    changed: bool,
+    // the cache generation the program was requested for
+    program: Option<(u64, Rc<ShaderProgram>)>,
+    textures: Vec<(String, Rc<RefCell<Texture>>)>,
+    uniforms: HashMap<String, Uniform>,
+    // uniforms already reported as missing from the program
+    warned_uniforms: HashSet<String>,
 }
 
-impl<'a> Material<'a> {
-    pub fn new() -> Material<'a> {
+impl Material {
+    pub fn new() -> Material {
         Material {
+            name: None,
             program: None,
-            vertex_shader: EMPTY,
-            fragment_shader: EMPTY,
+            vertex_shader: String::new(),
+            fragment_shader: String::new(),
+            defines: BTreeMap::new(),
+            changed: true,
+            blend_mode: BlendMode::Opaque,
+            render_state: RenderState::new(),
+            alpha_map: None,
+            textures: Vec::new(),
+            uniforms: HashMap::new(),
+            warned_uniforms: HashSet::new(),
+        }
+    }
+
+    pub fn with_shaders<V: Into<String>, F: Into<String>>(vertex_shader: V, fragment_shader: F) -> Material {
+        let mut material = Material::new();
+        material.set_shaders(vertex_shader, fragment_shader);
+        material
+    }
+
+    pub fn set_shaders<V: Into<String>, F: Into<String>>(&mut self, vertex_shader: V, fragment_shader: F) {
+        self.vertex_shader = vertex_shader.into();
+        self.fragment_shader = fragment_shader.into();
+        self.changed = true;
+    }
+
+    pub fn vertex_shader(&self) -> &str {
+        &self.vertex_shader
+    }
+
+    pub fn fragment_shader(&self) -> &str {
+        &self.fragment_shader
+    }
+
+    /// Injected as `#define name value` in both shaders.
+    pub fn set_define(&mut self, name: &str, value: &str) {
+        if self.defines.get(name).map(String::as_str) != Some(value) {
+            self.defines.insert(name.to_string(), value.to_string());
+            self.changed = true;
+        }
+    }
+
+    pub fn remove_define(&mut self, name: &str) {
+        if self.defines.remove(name).is_some() {
+            self.changed = true;
+        }
+    }
+
+    pub fn defines(&self) -> &BTreeMap<String, String> {
+        &self.defines
+    }
+
+    /// Defines the flag `name` when `enabled`, removes it otherwise.
+    pub fn toggle_define(&mut self, name: &str, enabled: bool) {
+        if enabled != self.defines.contains_key(name) {
+            if enabled {
+                self.set_define(name, "");
+            } else {
+                self.remove_define(name);
+            }
+        }
+    }
+
+    pub fn set_uniform<U: Into<Uniform>>(&mut self, name: &str, value: U) {
+        self.uniforms.insert(name.to_string(), value.into());
+    }
+
+    pub fn get_uniform(&self, name: &str) -> Option<&Uniform> {
+        self.uniforms.get(name)
+    }
+
+    pub fn remove_uniform(&mut self, name: &str) -> Option<Uniform> {
+        self.uniforms.remove(name)
+    }
+
+    /// With `warn`, reports once per program a uniform the program does not use.
+    fn uniform_location(&mut self, gl: &WebGl2RenderingContext, name: &str, warn: bool) -> Option<WebGlUniformLocation> {
+        locate_uniform(&self.program, &mut self.warned_uniforms, gl, name, warn)
+    }
+
+    /// Uploads a value without storing it on the material, for values that
+    /// change every draw such as the model matrix. The program must be in use.
+    pub fn upload_uniform(&mut self, gl: &WebGl2RenderingContext, name: &str, value: &Uniform) {
+        let location = self.uniform_location(gl, name, false);
+        value.upload(gl, location.as_ref());
+    }
+
+    /// Uploads values shared with other materials, without storing them. The
+    /// program must be in use.
+    pub fn upload_uniform_set(&mut self, gl: &WebGl2RenderingContext, set: &UniformSet) {
+        for (name, value) in set.iter() {
+            let location = self.uniform_location(gl, name, false);
+            value.upload(gl, location.as_ref());
+        }
+    }
+
+    /// Uploads every uniform set with `set_uniform`. The program must be in use.
+    pub fn upload_uniforms(&mut self, gl: &WebGl2RenderingContext) {
+        for (name, value) in self.uniforms.iter() {
+            let location = locate_uniform(&self.program, &mut self.warned_uniforms, gl, name, true);
+            value.upload(gl, location.as_ref());
+        }
+    }
+
+    /// Binds `texture` to the sampler uniform `name`, replacing any texture
+    /// previously bound to it.
+    pub fn set_texture(&mut self, name: &str, texture: &Rc<RefCell<Texture>>) {
+        match self.textures.iter_mut().find(|(n, _)| n == name) {
+            Some(binding) => binding.1 = texture.clone(),
+            None => self.textures.push((name.to_string(), texture.clone())),
+        }
+    }
+
+    pub fn remove_texture(&mut self, name: &str) {
+        self.textures.retain(|(n, _)| n != name);
+    }
+
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn get_texture(&self, name: &str) -> Option<&Rc<RefCell<Texture>>> {
+        self.textures.iter().find(|(n, _)| n == name).map(|(_, t)| t)
+    }
+
+    /// Binds the textures to consecutive texture units, in the order they
+    /// were added, and points their sampler uniforms at them. `max_units` is
+    /// the `MAX_COMBINED_TEXTURE_IMAGE_UNITS` of the context. The program must
+    /// be in use.
+    pub fn bind_textures(&mut self, gl: &WebGl2RenderingContext, max_units: usize) -> Result<(), JsValue> {
+        if self.textures.len() > max_units {
+            return Err(JsValue::from(format!(
+                "Material uses {} textures, only {} texture units are available",
+                self.textures.len(),
+                max_units
+            )));
         }
+
+        for (unit, (name, texture)) in self.textures.iter().enumerate() {
+            texture.borrow_mut().bind(gl, unit as u32)?;
+            let location = locate_uniform(&self.program, &mut self.warned_uniforms, gl, name, true);
+            Uniform::Sampler(unit as u32).upload(gl, location.as_ref());
+        }
+
+        Ok(())
     }
 
-    pub fn get_program(&mut self, gl: &WebGl2RenderingContext) -> Result<&WebGlProgram, JsValue> {
-        if self.program.is_none() {
-            let vert_shader = compile_shader(
-                gl,
-                WebGl2RenderingContext::VERTEX_SHADER,
-                self.vertex_shader,
-            )?;
-            let frag_shader = compile_shader(
-                gl,
-                WebGl2RenderingContext::FRAGMENT_SHADER,
-                self.fragment_shader,
-            )?;
-
-            let program = link_program(gl, &vert_shader, &frag_shader)?;
-            self.program = Some(program);
+    /// Gets the program for the current sources and defines from `programs`,
+    /// compiling it if no other material uses it yet. When the sources or
+    /// chunks change and the new program fails to build, the error is logged
+    /// and the material keeps drawing with its previous program.
+    pub fn get_program(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        programs: &mut ProgramCache,
+    ) -> Result<&ShaderProgram, ShaderError> {
+        let up_to_date = match &self.program {
+            Some((generation, _)) => !self.changed && *generation == programs.generation(),
+            None => false,
+        };
+
+        if !up_to_date {
+            let key = ProgramKey {
+                vertex_shader: self.vertex_shader.clone(),
+                fragment_shader: self.fragment_shader.clone(),
+                defines: self.defines.clone(),
+            };
+            self.changed = false;
+            match programs.get(gl, &key) {
+                Ok(program) => {
+                    // the warnings are per program
+                    let same = matches!(&self.program, Some((_, previous)) if Rc::ptr_eq(previous, &program));
+                    if !same {
+                        self.warned_uniforms.clear();
+                    }
+                    self.program = Some((programs.generation(), program));
+                }
+                Err(error) => match self.program.take() {
+                    // up to date with the failure, so the error is reported once
+                    Some((_, previous)) => {
+                        let message = format!("Keeping the previous program: {}", error);
+                        console_error!(message);
+                        self.program = Some((programs.generation(), previous));
+                    }
+                    None => return Err(error),
+                },
+            }
         }
 
-        let a = self.program.as_ref().unwrap();
-        Ok(a)
+        Ok(&self.program.as_ref().unwrap().1)
     }
 }
 
-pub fn compile_shader(
+/// Takes the fields it needs rather than the material, so that callers can
+/// iterate over the others.
+fn locate_uniform(
+    program: &Option<(u64, Rc<ShaderProgram>)>,
+    warned_uniforms: &mut HashSet<String>,
+    gl: &WebGl2RenderingContext,
+    name: &str,
+    warn: bool,
+) -> Option<WebGlUniformLocation> {
+    let (_, program) = program.as_ref()?;
+    if warn && program.uniform(name).is_none() && warned_uniforms.insert(name.to_string()) {
+        let message = format!("Material uniform '{}' is not an active uniform of its program", name);
+        console_warn!(message);
+    }
+    program.uniform_location(gl, name)
+}
+
+/// Compiles preprocessed source, reporting errors against the lines of the
+/// sources and chunks it was built from.
+pub fn compile_preprocessed_shader(
     context: &WebGl2RenderingContext,
     shader_type: u32,
-    source: &str,
-) -> Result<WebGlShader, String> {
+    source: &Preprocessed,
+) -> Result<WebGlShader, ShaderError> {
     let shader = context
         .create_shader(shader_type)
-        .ok_or_else(|| String::from("Unable to create shader object"))?;
-    context.shader_source(&shader, source);
+        .ok_or_else(|| ShaderError::Create(String::from("Unable to create shader object")))?;
+    context.shader_source(&shader, &source.source);
     context.compile_shader(&shader);
 
     if context
@@ -61,9 +299,11 @@ pub fn compile_shader(
     {
         Ok(shader)
     } else {
-        Err(context
+        let log = context
             .get_shader_info_log(&shader)
-            .unwrap_or_else(|| String::from("Unknown error creating shader")))
+            .unwrap_or_else(|| String::from("Unknown error creating shader"));
+        context.delete_shader(Some(&shader));
+        Err(ShaderError::compile(ShaderStage::from_gl(shader_type), &log, source))
     }
 }
 
@@ -71,13 +311,16 @@ pub fn link_program(
     context: &WebGl2RenderingContext,
     vert_shader: &WebGlShader,
     frag_shader: &WebGlShader,
-) -> Result<WebGlProgram, String> {
+) -> Result<WebGlProgram, ShaderError> {
     let program = context
         .create_program()
-        .ok_or_else(|| String::from("Unable to create shader object"))?;
+        .ok_or_else(|| ShaderError::Create(String::from("Unable to create program object")))?;
 
     context.attach_shader(&program, vert_shader);
     context.attach_shader(&program, frag_shader);
+    for (name, location) in ATTRIBUTE_LOCATIONS.iter() {
+        context.bind_attrib_location(&program, *location, name);
+    }
     context.link_program(&program);
 
     if context
@@ -87,8 +330,10 @@ pub fn link_program(
     {
         Ok(program)
     } else {
-        Err(context
+        let log = context
             .get_program_info_log(&program)
-            .unwrap_or_else(|| String::from("Unknown error creating program object")))
+            .unwrap_or_else(|| String::from("Unknown error creating program object"));
+        context.delete_program(Some(&program));
+        Err(ShaderError::Link { log })
     }
 }
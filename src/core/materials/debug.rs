@@ -0,0 +1,75 @@
+use super::STANDARD_VERTEX_SHADER;
+use crate::core::material::Material;
+
+const FRAGMENT_SHADER: &str = include_str!("../shaders/debug_fragment.glsl");
+
+/// Materials showing a vertex attribute or a derived value as a color.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugMaterial {
+    /// World space normals, each axis mapped from [-1, 1] to [0, 1].
+    Normal,
+    /// Texture coordinates in red and green, repeating outside [0, 1].
+    Uv,
+    /// Distance to the camera, white at `near` and black from `far`.
+    Depth { near: f32, far: f32 },
+    /// World position times `scale`, repeating every unit.
+    WorldPosition { scale: f32 },
+}
+
+impl DebugMaterial {
+    pub fn build(&self) -> Material {
+        let mut material = Material::with_shaders(STANDARD_VERTEX_SHADER, FRAGMENT_SHADER);
+        self.apply(&mut material);
+        material
+    }
+
+    pub fn apply(&self, material: &mut Material) {
+        // only the defines and uniforms of the current mode are kept
+        let define = match self {
+            DebugMaterial::Normal => "DEBUG_NORMAL",
+            DebugMaterial::Uv => "DEBUG_UV",
+            DebugMaterial::Depth { .. } => "DEBUG_DEPTH",
+            DebugMaterial::WorldPosition { .. } => "DEBUG_WORLD_POSITION",
+        };
+        for name in &["DEBUG_NORMAL", "DEBUG_UV", "DEBUG_DEPTH", "DEBUG_WORLD_POSITION"] {
+            material.toggle_define(name, *name == define);
+        }
+        for name in &["near", "far", "positionScale"] {
+            material.remove_uniform(name);
+        }
+
+        match *self {
+            DebugMaterial::Normal | DebugMaterial::Uv => {}
+            DebugMaterial::Depth { near, far } => {
+                material.set_uniform("near", near);
+                material.set_uniform("far", far);
+            }
+            DebugMaterial::WorldPosition { scale } => {
+                material.set_uniform("positionScale", scale);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_switches_mode() {
+        let mut material = DebugMaterial::Depth { near: 0.1, far: 100.0 }.build();
+        assert!(material.defines().contains_key("DEBUG_DEPTH"));
+        assert!(material.get_uniform("far").is_some());
+
+        DebugMaterial::WorldPosition { scale: 2.0 }.apply(&mut material);
+        assert_eq!(material.defines().len(), 1);
+        assert!(material.defines().contains_key("DEBUG_WORLD_POSITION"));
+        assert!(material.get_uniform("near").is_none());
+        assert!(material.get_uniform("far").is_none());
+        assert!(material.get_uniform("positionScale").is_some());
+
+        DebugMaterial::Normal.apply(&mut material);
+        assert!(material.defines().contains_key("DEBUG_NORMAL"));
+        assert!(material.get_uniform("positionScale").is_none());
+    }
+}
@@ -0,0 +1,59 @@
+//! Built-in materials. Each type holds the parameters of a shading model and
+//! builds a `Material` from them, `apply` updates a material after the
+//! parameters changed.
+
+use std::{cell::RefCell, rc::Rc};
+
+use super::{material::Material, texture::Texture};
+
+pub mod debug;
+pub mod pbr;
+pub mod phong;
+pub mod unlit;
+
+/// Vertex shader of the built-in materials, passes the world position, world
+/// normal and uv to the fragment shader.
+pub const STANDARD_VERTEX_SHADER: &str = include_str!("../shaders/standard_vertex.glsl");
+
+/// Binds `texture` to the sampler `name` and toggles `define` with it.
+fn set_map(material: &mut Material, name: &str, define: &str, texture: &Option<Rc<RefCell<Texture>>>) {
+    match texture {
+        Some(texture) => {
+            material.set_texture(name, texture);
+            material.set_define(define, "");
+        }
+        None => {
+            material.remove_texture(name);
+            material.remove_define(define);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{debug::DebugMaterial, pbr::PbrMaterial, phong::PhongMaterial, unlit::UnlitMaterial};
+    use crate::core::preprocessor::{preprocess, ShaderChunks};
+
+    #[test]
+    fn test_sources_preprocess() {
+        let chunks = ShaderChunks::new();
+        let materials = [
+            PhongMaterial::new().build(),
+            PbrMaterial::new().build(),
+            UnlitMaterial::new().build(),
+            DebugMaterial::Normal.build(),
+            DebugMaterial::Depth { near: 0.1, far: 100.0 }.build(),
+        ];
+        for material in &materials {
+            let mut shadowed = material.defines().clone();
+            shadowed.insert("USE_SHADOWS".to_string(), String::new());
+            shadowed.insert("ALPHA_TEST".to_string(), String::new());
+            for defines in &[material.defines(), &shadowed] {
+                for source in &[material.vertex_shader(), material.fragment_shader()] {
+                    let output = preprocess(source, defines, &chunks).unwrap();
+                    assert!(output.starts_with("#version 300 es\n"));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{vec3, vec4, Vector3, Vector4};
+
+use super::{set_map, STANDARD_VERTEX_SHADER};
+use crate::core::{material::Material, texture::Texture};
+
+const FRAGMENT_SHADER: &str = include_str!("../shaders/pbr_fragment.glsl");
+
+/// The glTF 2.0 metallic-roughness model with a Cook-Torrance BRDF. Factors
+/// multiply their map, color maps are sRGB and factors linear.
+///
+/// Lighting is computed in linear space. By default the result is clamped and
+/// encoded to sRGB for display; with `linear_output` the linear HDR color is
+/// written as is, for a `Composer` with a `tone_mapping` pass encoding it.
+pub struct PbrMaterial {
+    pub base_color: Vector4<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vector3<f32>,
+    /// Only used with a `normal_map`.
+    pub normal_scale: f32,
+    /// Only used with an `occlusion_map`.
+    pub occlusion_strength: f32,
+    pub base_color_map: Option<Rc<RefCell<Texture>>>,
+    /// Roughness in the green channel, metalness in the blue channel.
+    pub metallic_roughness_map: Option<Rc<RefCell<Texture>>>,
+    /// Tangent space normals.
+    pub normal_map: Option<Rc<RefCell<Texture>>>,
+    /// Occlusion in the red channel.
+    pub occlusion_map: Option<Rc<RefCell<Texture>>>,
+    pub emissive_map: Option<Rc<RefCell<Texture>>>,
+    /// Writes linear HDR colors, defines `LINEAR_OUTPUT`.
+    pub linear_output: bool,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> PbrMaterial {
+        PbrMaterial::new()
+    }
+}
+
+impl PbrMaterial {
+    /// The glTF defaults: a white, fully metallic and rough surface.
+    pub fn new() -> PbrMaterial {
+        PbrMaterial {
+            base_color: vec4(1.0, 1.0, 1.0, 1.0),
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: vec3(0.0, 0.0, 0.0),
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+            linear_output: false,
+        }
+    }
+
+    pub fn build(&self) -> Material {
+        let mut material = Material::with_shaders(STANDARD_VERTEX_SHADER, FRAGMENT_SHADER);
+        material.alpha_map = Some("baseColorMap".to_string());
+        self.apply(&mut material);
+        material
+    }
+
+    pub fn apply(&self, material: &mut Material) {
+        material.set_uniform("baseColor", self.base_color);
+        material.set_uniform("metallic", self.metallic);
+        material.set_uniform("roughness", self.roughness);
+        material.set_uniform("emissiveColor", self.emissive);
+
+        set_map(material, "baseColorMap", "USE_BASE_COLOR_MAP", &self.base_color_map);
+        set_map(
+            material,
+            "metallicRoughnessMap",
+            "USE_METALLIC_ROUGHNESS_MAP",
+            &self.metallic_roughness_map,
+        );
+        set_map(material, "normalMap", "USE_NORMAL_MAP", &self.normal_map);
+        set_map(material, "occlusionMap", "USE_OCCLUSION_MAP", &self.occlusion_map);
+        // only declared along with their map
+        set_map_factor(material, "normalScale", self.normal_scale, &self.normal_map);
+        set_map_factor(material, "occlusionStrength", self.occlusion_strength, &self.occlusion_map);
+        set_map(material, "emissiveMap", "USE_EMISSIVE_MAP", &self.emissive_map);
+        material.toggle_define("LINEAR_OUTPUT", self.linear_output);
+    }
+}
+
+fn set_map_factor(material: &mut Material, name: &str, factor: f32, map: &Option<Rc<RefCell<Texture>>>) {
+    if map.is_some() {
+        material.set_uniform(name, factor);
+    } else {
+        material.remove_uniform(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uniform::Uniform;
+
+    /// The field holding a map.
+    type MapField = fn(&mut PbrMaterial) -> &mut Option<Rc<RefCell<Texture>>>;
+
+    #[test]
+    fn test_apply_defines() {
+        let maps: [(&str, &str, MapField); 5] = [
+            ("baseColorMap", "USE_BASE_COLOR_MAP", |pbr| &mut pbr.base_color_map),
+            ("metallicRoughnessMap", "USE_METALLIC_ROUGHNESS_MAP", |pbr| &mut pbr.metallic_roughness_map),
+            ("normalMap", "USE_NORMAL_MAP", |pbr| &mut pbr.normal_map),
+            ("occlusionMap", "USE_OCCLUSION_MAP", |pbr| &mut pbr.occlusion_map),
+            ("emissiveMap", "USE_EMISSIVE_MAP", |pbr| &mut pbr.emissive_map),
+        ];
+
+        let mut pbr = PbrMaterial::new();
+        let mut material = pbr.build();
+        assert!(material.defines().is_empty());
+
+        for (name, define, map) in maps.iter() {
+            *map(&mut pbr) = Some(Rc::new(RefCell::new(Texture::from_rgba(1, 1, vec![255; 4]))));
+            pbr.apply(&mut material);
+            assert!(material.defines().contains_key(*define), "{}", define);
+            assert!(material.get_texture(name).is_some());
+            assert_eq!(material.defines().len(), 1);
+
+            *map(&mut pbr) = None;
+            pbr.apply(&mut material);
+            assert!(material.defines().is_empty());
+            assert_eq!(material.texture_count(), 0);
+        }
+
+        pbr.linear_output = true;
+        pbr.apply(&mut material);
+        assert!(material.defines().contains_key("LINEAR_OUTPUT"));
+    }
+
+    #[test]
+    fn test_map_factors_follow_their_map() {
+        let mut pbr = PbrMaterial::new();
+        let mut material = pbr.build();
+        assert!(material.get_uniform("normalScale").is_none());
+        assert!(material.get_uniform("occlusionStrength").is_none());
+
+        pbr.normal_map = Some(Rc::new(RefCell::new(Texture::from_rgba(1, 1, vec![255; 4]))));
+        pbr.normal_scale = 0.5;
+        pbr.apply(&mut material);
+        assert_eq!(material.get_uniform("normalScale"), Some(&Uniform::Float(0.5)));
+        assert!(material.get_uniform("occlusionStrength").is_none());
+
+        pbr.normal_map = None;
+        pbr.apply(&mut material);
+        assert!(material.get_uniform("normalScale").is_none());
+    }
+}
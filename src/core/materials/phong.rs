@@ -0,0 +1,107 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{vec3, Vector3};
+
+use super::{set_map, STANDARD_VERTEX_SHADER};
+use crate::core::{material::Material, texture::Texture};
+
+const FRAGMENT_SHADER: &str = include_str!("../shaders/phong_fragment.glsl");
+
+/// Phong shading, or Blinn-Phong with `blinn`. Colors are linear RGB, color
+/// maps are sRGB.
+///
+/// As with `PbrMaterial`, the lit result is clamped and encoded to sRGB unless
+/// `linear_output` is set.
+pub struct PhongMaterial {
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    /// Exponent of the specular highlight, higher is sharper.
+    pub shininess: f32,
+    pub emissive: Vector3<f32>,
+    /// Alpha of the fragments, blended with a transparent `blend_mode` on the
+    /// material.
+    pub opacity: f32,
+    /// Multiplies `diffuse`, its alpha multiplies `opacity`.
+    pub diffuse_map: Option<Rc<RefCell<Texture>>>,
+    /// Multiplies `specular`.
+    pub specular_map: Option<Rc<RefCell<Texture>>>,
+    pub blinn: bool,
+    /// Writes linear HDR colors, defines `LINEAR_OUTPUT`.
+    pub linear_output: bool,
+}
+
+impl Default for PhongMaterial {
+    fn default() -> PhongMaterial {
+        PhongMaterial::new()
+    }
+}
+
+impl PhongMaterial {
+    pub fn new() -> PhongMaterial {
+        PhongMaterial {
+            diffuse: vec3(1.0, 1.0, 1.0),
+            specular: vec3(0.1, 0.1, 0.1),
+            shininess: 30.0,
+            emissive: vec3(0.0, 0.0, 0.0),
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            blinn: true,
+            linear_output: false,
+        }
+    }
+
+    pub fn build(&self) -> Material {
+        let mut material = Material::with_shaders(STANDARD_VERTEX_SHADER, FRAGMENT_SHADER);
+        material.alpha_map = Some("diffuseMap".to_string());
+        self.apply(&mut material);
+        material
+    }
+
+    pub fn apply(&self, material: &mut Material) {
+        material.set_uniform("diffuseColor", self.diffuse);
+        material.set_uniform("specularColor", self.specular);
+        material.set_uniform("shininess", self.shininess);
+        material.set_uniform("emissiveColor", self.emissive);
+        material.set_uniform("opacity", self.opacity);
+
+        set_map(material, "diffuseMap", "USE_DIFFUSE_MAP", &self.diffuse_map);
+        set_map(material, "specularMap", "USE_SPECULAR_MAP", &self.specular_map);
+        material.toggle_define("USE_BLINN", self.blinn);
+        material.toggle_define("LINEAR_OUTPUT", self.linear_output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uniform::Uniform;
+
+    #[test]
+    fn test_apply() {
+        let mut phong = PhongMaterial::new();
+        phong.shininess = 8.0;
+        let mut material = phong.build();
+        assert_eq!(material.get_uniform("shininess"), Some(&Uniform::Float(8.0)));
+        assert_eq!(material.get_uniform("diffuseColor"), Some(&Uniform::Vec3(vec3(1.0, 1.0, 1.0))));
+        assert!(material.defines().contains_key("USE_BLINN"));
+        assert!(!material.defines().contains_key("USE_DIFFUSE_MAP"));
+        assert!(!material.defines().contains_key("LINEAR_OUTPUT"));
+        assert_eq!(material.texture_count(), 0);
+
+        phong.diffuse_map = Some(Rc::new(RefCell::new(Texture::from_rgba(1, 1, vec![255; 4]))));
+        phong.blinn = false;
+        phong.linear_output = true;
+        phong.apply(&mut material);
+        assert!(material.defines().contains_key("USE_DIFFUSE_MAP"));
+        assert!(material.defines().contains_key("LINEAR_OUTPUT"));
+        assert!(!material.defines().contains_key("USE_SPECULAR_MAP"));
+        assert!(!material.defines().contains_key("USE_BLINN"));
+        assert!(material.get_texture("diffuseMap").is_some());
+
+        phong.diffuse_map = None;
+        phong.apply(&mut material);
+        assert!(!material.defines().contains_key("USE_DIFFUSE_MAP"));
+        assert!(material.get_texture("diffuseMap").is_none());
+    }
+}
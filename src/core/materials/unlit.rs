@@ -0,0 +1,41 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{vec4, Vector4};
+
+use super::{set_map, STANDARD_VERTEX_SHADER};
+use crate::core::{material::Material, texture::Texture};
+
+const FRAGMENT_SHADER: &str = include_str!("../shaders/unlit_fragment.glsl");
+
+/// A flat color, multiplied by `map` when set. Ignores the lights.
+pub struct UnlitMaterial {
+    pub color: Vector4<f32>,
+    pub map: Option<Rc<RefCell<Texture>>>,
+}
+
+impl Default for UnlitMaterial {
+    fn default() -> UnlitMaterial {
+        UnlitMaterial::new()
+    }
+}
+
+impl UnlitMaterial {
+    pub fn new() -> UnlitMaterial {
+        UnlitMaterial {
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            map: None,
+        }
+    }
+
+    pub fn build(&self) -> Material {
+        let mut material = Material::with_shaders(STANDARD_VERTEX_SHADER, FRAGMENT_SHADER);
+        material.alpha_map = Some("map".to_string());
+        self.apply(&mut material);
+        material
+    }
+
+    pub fn apply(&self, material: &mut Material) {
+        material.set_uniform("color", self.color);
+        set_map(material, "map", "USE_MAP", &self.map);
+    }
+}
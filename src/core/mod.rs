@@ -1,7 +1,28 @@
 pub mod geometry;
+pub mod light;
 pub mod material;
+pub mod materials;
+pub mod render_queue;
+pub mod render_state;
+pub mod render_target;
 pub mod renderer;
 pub mod shapes;
+pub mod skybox;
+pub mod texture;
+pub mod uniform;
 pub mod scene;
+pub mod shader_error;
+pub mod shadow;
 pub mod object3d;
-pub mod graph;
\ No newline at end of file
+pub mod postprocessing;
+pub mod preprocessor;
+#[cfg(debug_assertions)]
+pub mod hot_reload;
+pub mod program;
+pub mod graph;
+pub mod app;
+pub mod background;
+pub mod fullscreen;
+pub mod clock;
+pub mod controls;
+pub mod input;
\ No newline at end of file
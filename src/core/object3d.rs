@@ -1,13 +1,35 @@
 use std::{cell::RefCell, rc::Rc};
 
-use cgmath::{vec3, Matrix4, Quaternion, SquareMatrix, Vector3};
+use cgmath::{
+    vec3, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, Rotation, Rotation3, SquareMatrix, Transform as _,
+    Vector3,
+};
+use wasm_bindgen::JsValue;
 use web_sys::WebGl2RenderingContext;
 use weblog::console_log;
 
-use super::{geometry::Geometry, graph::Node, material::Material, renderer::RenderingContext};
+use super::{
+    geometry::Geometry, graph::Node, light::Light, material::{BlendMode, Material}, renderer::RenderingContext, shadow::DepthContext,
+    uniform::Uniform,
+};
 
 pub trait Renderable {
-    fn render(&mut self, transform: &Transform, rendering_context: &RenderingContext);
+    fn render(&mut self, transform: &Transform, rendering_context: &RenderingContext) -> Result<(), JsValue>;
+
+    /// Transparent objects are drawn after the opaque ones, back to front.
+    fn is_transparent(&self) -> bool {
+        false
+    }
+
+    /// Draws the depth of the object into a shadow map, nothing by default.
+    fn render_depth(&mut self, _transform: &Transform, _context: &DepthContext) -> Result<(), JsValue> {
+        Ok(())
+    }
+
+    /// The material drawing the object, if it has a single one.
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        None
+    }
 }
 
 
@@ -28,6 +50,61 @@ impl Transform {
             matrix_world: Matrix4::identity(),
         }
     }
+
+    /// Rotates then translates, so `position` is expressed in the parent
+    /// space whatever the rotation.
+    pub fn update_matrix(&mut self) {
+        self.matrix = Matrix4::from_translation(self.position) * Matrix4::from(self.quaternion);
+    }
+
+    /// Moves along `axis` expressed in local space.
+    pub fn translate_on_axis(&mut self, axis: Vector3<f32>, distance: f32) {
+        self.position += self.quaternion.rotate_vector(axis.normalize()) * distance;
+    }
+
+    /// Rotates around `axis` expressed in local space.
+    pub fn rotate_on_axis(&mut self, axis: Vector3<f32>, angle: Rad<f32>) {
+        self.quaternion = self.quaternion * Quaternion::from_axis_angle(axis.normalize(), angle);
+    }
+
+    // The world getters and conversions below read `matrix_world`, which is
+    // refreshed by `Object3d::update` once per frame.
+
+    pub fn world_position(&self) -> Point3<f32> {
+        Point3::new(self.matrix_world.w.x, self.matrix_world.w.y, self.matrix_world.w.z)
+    }
+
+    pub fn world_quaternion(&self) -> Quaternion<f32> {
+        rotation_of(&self.matrix_world)
+    }
+
+    pub fn local_to_world(&self, point: Point3<f32>) -> Point3<f32> {
+        self.matrix_world.transform_point(point)
+    }
+
+    pub fn world_to_local(&self, point: Point3<f32>) -> Point3<f32> {
+        self.matrix_world.invert().unwrap_or_else(Matrix4::identity).transform_point(point)
+    }
+
+    pub fn local_to_world_direction(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        self.matrix_world.transform_vector(direction).normalize()
+    }
+
+    pub fn world_to_local_direction(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        self.matrix_world
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+            .transform_vector(direction)
+            .normalize()
+    }
+}
+
+/// Rotation part of an affine matrix, with any scale removed.
+fn rotation_of(m: &Matrix4<f32>) -> Quaternion<f32> {
+    let x = m.x.truncate().normalize();
+    let y = m.y.truncate().normalize();
+    let z = m.z.truncate().normalize();
+    Quaternion::from(Matrix3::from_cols(x, y, z))
 }
 
 
@@ -35,6 +112,10 @@ pub struct Object3d {
     pub name: Option<String>,
     pub transform: Transform,
     pub renderer: Option<RefCell<Box<dyn Renderable>>>,
+    pub light: Option<Light>,
+    /// Objects with a lower order are drawn first, opaque or transparent.
+    /// Objects of the same order are sorted by depth within each queue.
+    pub render_order: i32,
 }
 
 impl Object3d {
@@ -43,11 +124,13 @@ impl Object3d {
             name: None,
             transform: Transform::new(),
             renderer: None,
+            light: None,
+            render_order: 0,
         }
     }
 
     pub fn update(&mut self, node: &Rc<Node<Object3d>>) {
-        self.transform.matrix = Matrix4::from(self.transform.quaternion) * Matrix4::from_translation(self.transform.position);
+        self.transform.update_matrix();
 
         let parent = node.parent.borrow().upgrade();
         if parent.is_none() {
@@ -57,88 +140,237 @@ impl Object3d {
         }
     }
 
+    fn parent_matrix_world(node: &Rc<Node<Object3d>>) -> Matrix4<f32> {
+        match node.parent.borrow().upgrade() {
+            Some(parent) => parent.value.borrow().transform.matrix_world,
+            None => Matrix4::identity(),
+        }
+    }
+
+    pub fn set_world_position(node: &Rc<Node<Object3d>>, position: Point3<f32>) {
+        let parent_inverse = Object3d::parent_matrix_world(node).invert().unwrap_or_else(Matrix4::identity);
+        let local = parent_inverse.transform_point(position);
+
+        let mut object = node.value.borrow_mut();
+        object.transform.position = vec3(local.x, local.y, local.z);
+        object.update(node);
+    }
+
+    pub fn set_world_quaternion(node: &Rc<Node<Object3d>>, quaternion: Quaternion<f32>) {
+        let parent_rotation = rotation_of(&Object3d::parent_matrix_world(node));
+
+        let mut object = node.value.borrow_mut();
+        object.transform.quaternion = parent_rotation.invert() * quaternion;
+        object.update(node);
+    }
+
+    /// Orients the node so that its local -Z axis points at `target`, the
+    /// convention the renderer uses for cameras. Leaves the rotation unchanged
+    /// when the node already sits on `target`.
+    pub fn look_at(node: &Rc<Node<Object3d>>, target: Point3<f32>, up: Vector3<f32>) {
+        let eye = {
+            let mut object = node.value.borrow_mut();
+            object.update(node);
+            object.transform.world_position()
+        };
+
+        let offset = eye - target;
+        if offset.magnitude2() < f32::EPSILON {
+            return;
+        }
+
+        let z = offset.normalize();
+        let mut x = up.cross(z);
+        if x.magnitude2() < f32::EPSILON {
+            // up is parallel to the view direction, pick any perpendicular axis
+            x = if z.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() }.cross(z);
+        }
+        let x = x.normalize();
+        let y = z.cross(x);
+
+        let rotation = Quaternion::from(Matrix3::from_cols(x, y, z));
+        Object3d::set_world_quaternion(node, rotation);
+    }
 }
 
 
 
 
-pub struct Mesh<'a> {
-    pub material: Material<'a>,
+pub struct Mesh {
+    pub material: Material,
     pub geometry: Geometry,
+    /// Drawn into the shadow maps of the lights.
+    pub cast_shadow: bool,
+    /// Darkened by the shadows of other meshes, or of itself.
+    pub receive_shadow: bool,
 }
 
-impl<'a> Mesh<'a> {
-    pub fn new(material: Material<'a>, geometry: Geometry) -> Mesh {
+impl Mesh {
+    pub fn new(material: Material, geometry: Geometry) -> Mesh {
         Mesh {
-            material: material,
-            geometry: geometry,
+            material,
+            geometry,
+            cast_shadow: true,
+            receive_shadow: true,
         }
     }
+
+    fn draw_elements(&mut self, gl: &WebGl2RenderingContext) {
+        let vertices_count = self.geometry.indexes.as_ref().unwrap().len();
+        gl.draw_elements_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            vertices_count as i32,
+            WebGl2RenderingContext::UNSIGNED_SHORT,
+            0,
+        );
+    }
 }
 
-impl Renderable for Mesh<'_> {
-    fn render(&mut self, transform: &Transform, rendering_context: &RenderingContext) {
+impl Renderable for Mesh {
+    fn render(&mut self, transform: &Transform, rendering_context: &RenderingContext) -> Result<(), JsValue> {
         let gl = rendering_context.gl;
 
-        let light_direction = [-0.5, 0.5, 0.5];
-        let ambient_color = [1.0, 1.0, 1.0, 1.0];
-
         let eye = vec3(
             rendering_context.camera_matrix.w.x,
             rendering_context.camera_matrix.w.y,
             rendering_context.camera_matrix.w.z,
         );
-        // console_log!(format!("eye2={:?}", eye));
 
         let camera_matrix_invert = rendering_context.camera_matrix.invert().unwrap();
         let view_matrix: Matrix4<f32> = camera_matrix_invert;
         let model_view_matrix: Matrix4<f32> = camera_matrix_invert * transform.matrix_world;
 
+        let shadows = self.receive_shadow && rendering_context.shadows.is_active();
         let mat = &mut self.material;
+        mat.toggle_define("USE_SHADOWS", shadows);
+        mat.toggle_define("ALPHA_TEST", matches!(mat.blend_mode, BlendMode::AlphaTest(_)));
+
         let geo = &mut self.geometry;
-        let vertices_count = geo.indexes.as_ref().unwrap().len();
-        let program = mat.get_program(gl).unwrap();
-        let vao = geo.get_vao(gl, program).unwrap();
-
-        let uni_location = [
-            gl.get_uniform_location(&program, "modelMatrix"),
-            gl.get_uniform_location(&program, "modelViewMatrix"),
-            gl.get_uniform_location(&program, "projectionMatrix"),
-            gl.get_uniform_location(&program, "viewMatrix"),
-            gl.get_uniform_location(&program, "cameraPosition"),
-            gl.get_uniform_location(&program, "lightDirection"),
-            gl.get_uniform_location(&program, "ambientColor"),
-        ];
-
-        gl.use_program(Some(program));
+        let program = mat.get_program(gl, &mut rendering_context.programs.borrow_mut())?;
+        let vao = geo.get_vao(gl, program)?;
+        let program = program.program.clone();
+
+        gl.use_program(Some(&program));
         gl.bind_vertex_array(Some(vao));
+        // the shadow map takes the unit after the material textures
+        let texture_units = rendering_context.max_texture_units - shadows as usize;
+        mat.bind_textures(gl, texture_units)?;
+        mat.upload_uniforms(gl);
+        rendering_context
+            .state
+            .borrow_mut()
+            .apply(gl, &mat.render_state, mat.blend_mode);
+        if let BlendMode::AlphaTest(cutoff) = mat.blend_mode {
+            mat.upload_uniform(gl, "alphaTest", &Uniform::Float(cutoff));
+        }
 
-        gl.uniform_matrix4fv_with_f32_array(
-            uni_location[0].as_ref(),
-            false,
-            transform.matrix_world.as_ref() as &[f32; 16],
-        );
-        gl.uniform_matrix4fv_with_f32_array(
-            uni_location[1].as_ref(),
-            false,
-            model_view_matrix.as_ref() as &[f32; 16],
-        );
-        gl.uniform_matrix4fv_with_f32_array(
-            uni_location[2].as_ref(),
-            false,
-            rendering_context.projection_matrix.as_ref() as &[f32; 16],
-        );
-        gl.uniform_matrix4fv_with_f32_array(uni_location[3].as_ref(), false, view_matrix.as_ref() as &[f32; 16]);
-        gl.uniform3fv_with_f32_array(uni_location[4].as_ref(), &eye.as_ref() as &[f32; 3]);
-        gl.uniform3fv_with_f32_array(uni_location[5].as_ref(), &light_direction);
-        gl.uniform4fv_with_f32_array(uni_location[6].as_ref(), &ambient_color);
-        gl.draw_elements_with_i32(
-            WebGl2RenderingContext::TRIANGLES,
-            vertices_count as i32,
-            WebGl2RenderingContext::UNSIGNED_SHORT,
-            0,
-        );
+        mat.upload_uniform(gl, "modelMatrix", &Uniform::Mat4(transform.matrix_world));
+        mat.upload_uniform(gl, "modelViewMatrix", &Uniform::Mat4(model_view_matrix));
+        mat.upload_uniform(gl, "projectionMatrix", &Uniform::Mat4(*rendering_context.projection_matrix));
+        mat.upload_uniform(gl, "viewMatrix", &Uniform::Mat4(view_matrix));
+        mat.upload_uniform(gl, "cameraPosition", &Uniform::Vec3(eye));
+        mat.upload_uniform_set(gl, rendering_context.light_uniforms);
+        if shadows {
+            let unit = mat.texture_count() as u32;
+            rendering_context.shadows.bind(gl, mat, unit);
+            mat.upload_uniform_set(gl, rendering_context.shadow_uniforms);
+        }
+        self.draw_elements(gl);
 
         gl.bind_vertex_array(None);
+
+        Ok(())
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.material.blend_mode.is_transparent()
+    }
+
+    fn render_depth(&mut self, transform: &Transform, context: &DepthContext) -> Result<(), JsValue> {
+        if !self.cast_shadow {
+            return Ok(());
+        }
+
+        let gl = context.gl;
+        let alpha_map = match self.material.blend_mode {
+            BlendMode::AlphaTest(cutoff) => self
+                .material
+                .alpha_map
+                .as_ref()
+                .and_then(|name| self.material.get_texture(name))
+                .map(|texture| (cutoff, texture.clone())),
+            _ => None,
+        };
+        let program = match &alpha_map {
+            Some(_) => context.alpha_test_program,
+            None => context.program,
+        };
+
+        let vao = self.geometry.get_vao(gl, program)?;
+        gl.use_program(Some(&program.program));
+        gl.bind_vertex_array(Some(vao));
+        let location = |name: &str| program.uniform_location(gl, name);
+        Uniform::Mat4(context.light_view_projection).upload(gl, location("lightViewProjection").as_ref());
+        Uniform::Mat4(transform.matrix_world).upload(gl, location("modelMatrix").as_ref());
+        if let Some((cutoff, texture)) = alpha_map {
+            texture.borrow_mut().bind(gl, 0)?;
+            Uniform::Sampler(0).upload(gl, location("alphaMap").as_ref());
+            Uniform::Float(cutoff).upload(gl, location("alphaTest").as_ref());
+        }
+        self.draw_elements(gl);
+        gl.bind_vertex_array(None);
+
+        Ok(())
+    }
+
+    fn material_mut(&mut self) -> Option<&mut Material> {
+        Some(&mut self.material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, point3, Deg};
+
+    #[test]
+    fn test_world_conversions() {
+        let parent = Node::new_rc(Object3d::new());
+        let child = Node::new_rc(Object3d::new());
+        Node::add_child(&parent, &child);
+
+        parent.value.borrow_mut().transform.position = vec3(0.0, 0.0, -5.0);
+        parent.value.borrow_mut().transform.quaternion = Quaternion::from_angle_y(Deg(90.0));
+        parent.value.borrow_mut().update(&parent);
+
+        assert_abs_diff_eq!(parent.value.borrow().transform.world_position(), point3(0.0, 0.0, -5.0), epsilon = 1e-5);
+
+        // the parent's local +X axis points to world -Z
+        child.value.borrow_mut().transform.position = vec3(1.0, 0.0, 0.0);
+        child.value.borrow_mut().update(&child);
+        assert_abs_diff_eq!(child.value.borrow().transform.world_position(), point3(0.0, 0.0, -6.0), epsilon = 1e-5);
+
+        Object3d::set_world_position(&child, point3(1.0, 2.0, 3.0));
+        let c = child.value.borrow();
+        assert_abs_diff_eq!(c.transform.position, vec3(-8.0, 2.0, 1.0), epsilon = 1e-5);
+        assert_abs_diff_eq!(c.transform.world_position(), point3(1.0, 2.0, 3.0), epsilon = 1e-5);
+
+        let p = c.transform.world_to_local(point3(4.0, 4.0, 4.0));
+        assert_abs_diff_eq!(c.transform.local_to_world(p), point3(4.0, 4.0, 4.0), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let camera = Node::new_rc(Object3d::new());
+        camera.value.borrow_mut().transform.position = vec3(0.0, 7.5, 15.0);
+        Object3d::look_at(&camera, point3(0.0, 0.0, 0.0), Vector3::unit_y());
+
+        let forward = camera.value.borrow().transform.local_to_world_direction(-Vector3::unit_z());
+        assert_abs_diff_eq!(forward, vec3(0.0, -7.5, -15.0).normalize(), epsilon = 1e-5);
+
+        // looking at its own position keeps the previous orientation
+        Object3d::look_at(&camera, point3(0.0, 7.5, 15.0), Vector3::unit_y());
+        let forward = camera.value.borrow().transform.local_to_world_direction(-Vector3::unit_z());
+        assert_abs_diff_eq!(forward, vec3(0.0, -7.5, -15.0).normalize(), epsilon = 1e-5);
     }
 }
@@ -0,0 +1,153 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::vec2;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext as GL;
+
+use super::{Pass, PassContext};
+use crate::core::{
+    fullscreen::FULLSCREEN_VERTEX_SHADER,
+    material::Material,
+    render_target::{RenderTarget, RenderTargetOptions},
+    texture::{Texture, TextureFormat},
+};
+
+/// Adds a blurred copy of the bright parts of the image on top of it. Runs
+/// at half resolution, before tone mapping so that HDR highlights bloom.
+pub struct BloomPass {
+    /// Brightness above which pixels bloom.
+    pub threshold: f32,
+    /// Softens the cut at the threshold.
+    pub knee: f32,
+    pub strength: f32,
+    /// Blur iterations, each one widens the glow.
+    pub iterations: u32,
+    pub enabled: bool,
+
+    threshold_material: Material,
+    blur_material: Material,
+    composite_material: Material,
+    // ping-pong targets at half resolution
+    targets: [Rc<RefCell<RenderTarget>>; 2],
+    // replaced by set_format, freed on next render
+    retired: Vec<Rc<RefCell<RenderTarget>>>,
+}
+
+fn target(format: TextureFormat, width: u32, height: u32) -> Rc<RefCell<RenderTarget>> {
+    let mut options = RenderTargetOptions::new();
+    options.colors = vec![format];
+    options.depth = None;
+    Rc::new(RefCell::new(RenderTarget::new(width, height, options)))
+}
+
+impl BloomPass {
+    /// The targets use the format of the composer it is added to, HDR
+    /// highlights need a float format such as `TextureFormat::Rgba16F`.
+    pub fn new(threshold: f32, strength: f32) -> BloomPass {
+        BloomPass {
+            threshold,
+            knee: 0.5,
+            strength,
+            iterations: 2,
+            enabled: true,
+            threshold_material: Material::with_shaders(
+                FULLSCREEN_VERTEX_SHADER,
+                include_str!("../shaders/bloom_threshold_fragment.glsl"),
+            ),
+            blur_material: Material::with_shaders(FULLSCREEN_VERTEX_SHADER, include_str!("../shaders/blur_fragment.glsl")),
+            composite_material: Material::with_shaders(
+                FULLSCREEN_VERTEX_SHADER,
+                include_str!("../shaders/bloom_composite_fragment.glsl"),
+            ),
+            targets: [target(TextureFormat::Rgba16F, 1, 1), target(TextureFormat::Rgba16F, 1, 1)],
+            retired: Vec::new(),
+        }
+    }
+}
+
+impl Pass for BloomPass {
+    fn render(&mut self, context: &PassContext, input: &Rc<RefCell<Texture>>) -> Result<(), JsValue> {
+        for target in self.retired.drain(..) {
+            target.borrow_mut().dispose(context.gl);
+        }
+
+        let [a, b] = &self.targets;
+        let size = {
+            let a = a.borrow();
+            vec2(a.width() as f32, a.height() as f32)
+        };
+
+        self.threshold_material.set_texture("inputTexture", input);
+        self.threshold_material.set_uniform("threshold", self.threshold);
+        self.threshold_material.set_uniform("knee", self.knee);
+        a.borrow_mut().bind(context.gl)?;
+        context.draw(&mut self.threshold_material)?;
+
+        self.blur_material.set_uniform("inputSize", size);
+        for _ in 0..self.iterations.max(1) {
+            for (from, to, direction) in [(a, b, vec2(1.0, 0.0)), (b, a, vec2(0.0, 1.0))].iter() {
+                self.blur_material.set_texture("inputTexture", from.borrow().texture(0));
+                self.blur_material.set_uniform("direction", *direction);
+                to.borrow_mut().bind(context.gl)?;
+                context.draw(&mut self.blur_material)?;
+            }
+        }
+
+        self.composite_material.set_texture("inputTexture", input);
+        self.composite_material.set_texture("bloomTexture", a.borrow().texture(0));
+        self.composite_material.set_uniform("strength", self.strength);
+        context.bind_output()?;
+        context.draw(&mut self.composite_material)
+    }
+
+    fn set_size(&mut self, width: u32, height: u32) {
+        for target in self.targets.iter() {
+            target.borrow_mut().set_size((width / 2).max(1), (height / 2).max(1));
+        }
+    }
+
+    fn set_format(&mut self, format: TextureFormat) {
+        let (width, height) = {
+            let target = self.targets[0].borrow();
+            if target.options.colors == [format] {
+                return;
+            }
+            (target.width(), target.height())
+        };
+        let targets = [target(format, width, height), target(format, width, height)];
+        self.retired.extend(std::mem::replace(&mut self.targets, targets));
+    }
+
+    fn dispose(&mut self, gl: &GL) {
+        for target in self.targets.iter().chain(self.retired.iter()) {
+            target.borrow_mut().dispose(gl);
+        }
+        self.retired.clear();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets() {
+        let mut bloom = BloomPass::new(1.0, 0.5);
+        bloom.set_size(101, 50);
+        bloom.set_format(TextureFormat::Rgba8);
+        for target in bloom.targets.iter() {
+            let target = target.borrow();
+            assert_eq!((target.width(), target.height()), (50, 25));
+            assert_eq!(target.options.colors, vec![TextureFormat::Rgba8]);
+        }
+        // the Rgba16F targets are kept until a context can free them
+        assert_eq!(bloom.retired.len(), 2);
+
+        bloom.set_format(TextureFormat::Rgba8);
+        assert_eq!(bloom.retired.len(), 2);
+    }
+}
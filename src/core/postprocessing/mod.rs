@@ -0,0 +1,353 @@
+//! Full screen passes run on the rendered scene before it is presented.
+//!
+//! With a `Composer` set on the renderer, `Renderer::draw` renders the scene
+//! into an offscreen target, then each enabled pass reads the output of the
+//! previous one and the last pass writes to the canvas, or to the renderer's
+//! render target.
+
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::vec2;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext as GL;
+
+use super::{
+    fullscreen::{FullscreenTriangle, FULLSCREEN_VERTEX_SHADER},
+    material::Material,
+    program::ProgramCache,
+    render_target::{RenderTarget, RenderTargetOptions},
+    texture::{Texture, TextureFormat},
+    uniform::UniformSet,
+};
+
+pub mod bloom;
+pub mod passes;
+
+
+/// What a pass draws with.
+pub struct PassContext<'a> {
+    pub gl: &'a GL,
+    pub programs: &'a RefCell<ProgramCache>,
+    pub max_texture_units: usize,
+    /// `None` for the canvas.
+    pub output: Option<&'a Rc<RefCell<RenderTarget>>>,
+    /// Size of the output in pixels.
+    pub width: u32,
+    pub height: u32,
+    fullscreen: &'a FullscreenTriangle,
+}
+
+impl PassContext<'_> {
+    /// Binds the output of the pass, passes drawing to intermediate targets
+    /// call it before their last draw.
+    pub fn bind_output(&self) -> Result<(), JsValue> {
+        match self.output {
+            Some(target) => target.borrow_mut().bind(self.gl),
+            None => {
+                self.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+                self.gl.viewport(0, 0, self.width as i32, self.height as i32);
+                Ok(())
+            }
+        }
+    }
+
+    /// Draws a full screen triangle with `material` into the bound framebuffer,
+    /// with the output size in the `resolution` uniform. The material vertex
+    /// shader should be `FULLSCREEN_VERTEX_SHADER`.
+    pub fn draw(&self, material: &mut Material) -> Result<(), JsValue> {
+        let mut uniforms = UniformSet::new();
+        uniforms.set("resolution", vec2(self.width as f32, self.height as f32));
+        self.fullscreen
+            .draw(self.gl, self.programs, self.max_texture_units, material, &uniforms)
+    }
+}
+
+pub trait Pass {
+    /// Reads `input` and draws into the output of `context`.
+    fn render(&mut self, context: &PassContext, input: &Rc<RefCell<Texture>>) -> Result<(), JsValue>;
+
+    /// Called with the canvas size, for passes owning render targets.
+    fn set_size(&mut self, _width: u32, _height: u32) {}
+
+    /// Called with the format of the composer buffers, for passes owning
+    /// render targets.
+    fn set_format(&mut self, _format: TextureFormat) {}
+
+    /// Frees the GL resources owned by the pass, when its composer is
+    /// disposed.
+    fn dispose(&mut self, _gl: &GL) {}
+
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+/// A pass running a fragment shader over the input, bound to the
+/// `inputTexture` sampler.
+/// Parameters are uniforms and defines of `material`.
+pub struct ShaderPass {
+    pub material: Material,
+    pub enabled: bool,
+}
+
+impl ShaderPass {
+    pub fn new<F: Into<String>>(fragment_shader: F) -> ShaderPass {
+        ShaderPass {
+            material: Material::with_shaders(FULLSCREEN_VERTEX_SHADER, fragment_shader),
+            enabled: true,
+        }
+    }
+}
+
+impl Pass for ShaderPass {
+    fn render(&mut self, context: &PassContext, input: &Rc<RefCell<Texture>>) -> Result<(), JsValue> {
+        self.material.set_texture("inputTexture", input);
+        context.bind_output()?;
+        context.draw(&mut self.material)
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Runs a chain of passes over the rendered scene.
+pub struct Composer {
+    pub passes: Vec<Box<dyn Pass>>,
+
+    // the scene is rendered here, with a depth buffer
+    scene: Rc<RefCell<RenderTarget>>,
+    // the passes alternate between them
+    buffers: [Rc<RefCell<RenderTarget>>; 2],
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+impl Composer {
+    /// Buffers use `format`, `TextureFormat::Rgba16F` keeps the HDR range
+    /// for bloom and tone mapping. The scene is rendered with `samples`
+    /// samples per pixel.
+    pub fn new(format: TextureFormat, samples: i32) -> Composer {
+        let mut scene_options = RenderTargetOptions::new();
+        scene_options.colors = vec![format];
+        scene_options.samples = samples;
+
+        let mut buffer_options = RenderTargetOptions::new();
+        buffer_options.colors = vec![format];
+        buffer_options.depth = None;
+        let buffer = || Rc::new(RefCell::new(RenderTarget::new(1, 1, buffer_options.clone())));
+
+        Composer {
+            passes: Vec::new(),
+            scene: Rc::new(RefCell::new(RenderTarget::new(1, 1, scene_options))),
+            buffers: [buffer(), buffer()],
+            format,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    pub fn add_pass<P: Pass + 'static>(&mut self, mut pass: P) {
+        pass.set_format(self.format);
+        pass.set_size(self.width, self.height);
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Whether the scene goes through the passes, without enabled passes it is
+    /// drawn directly.
+    pub fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| pass.is_enabled())
+    }
+
+    /// Frees the render targets of the composer and of its passes.
+    pub fn dispose(&mut self, gl: &GL) {
+        for target in self.buffers.iter().chain(std::iter::once(&self.scene)) {
+            target.borrow_mut().dispose(gl);
+        }
+        for pass in self.passes.iter_mut() {
+            pass.dispose(gl);
+        }
+    }
+
+    /// The target the scene is rendered into.
+    pub fn scene_target(&self) -> &Rc<RefCell<RenderTarget>> {
+        &self.scene
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.scene.borrow_mut().set_size(width, height);
+        for buffer in self.buffers.iter() {
+            buffer.borrow_mut().set_size(width, height);
+        }
+        for pass in self.passes.iter_mut() {
+            pass.set_size(width, height);
+        }
+    }
+
+    /// Runs the passes over the scene target into `output`, `None` for the
+    /// canvas.
+    pub fn render(
+        &mut self,
+        gl: &GL,
+        programs: &RefCell<ProgramCache>,
+        max_texture_units: usize,
+        fullscreen: &FullscreenTriangle,
+        output: Option<&Rc<RefCell<RenderTarget>>>,
+    ) -> Result<(), JsValue> {
+        gl.disable(GL::DEPTH_TEST);
+        gl.depth_mask(false);
+        let result = self.render_passes(gl, programs, max_texture_units, fullscreen, output);
+        gl.enable(GL::DEPTH_TEST);
+        gl.depth_mask(true);
+        result
+    }
+
+    fn render_passes(
+        &mut self,
+        gl: &GL,
+        programs: &RefCell<ProgramCache>,
+        max_texture_units: usize,
+        fullscreen: &FullscreenTriangle,
+        output: Option<&Rc<RefCell<RenderTarget>>>,
+    ) -> Result<(), JsValue> {
+        let count = self.passes.iter().filter(|pass| pass.is_enabled()).count();
+
+        let passes = self.passes.iter_mut().filter(|pass| pass.is_enabled());
+        for (i, pass) in passes.enumerate() {
+            let (read, write) = pass_buffers(i, count);
+            let input = match read {
+                Some(n) => self.buffers[n].borrow().texture(0).clone(),
+                None => self.scene.borrow().texture(0).clone(),
+            };
+            let target = match write {
+                Some(n) => Some(&self.buffers[n]),
+                None => output,
+            };
+            let (width, height) = match target {
+                Some(target) => {
+                    let target = target.borrow();
+                    (target.width(), target.height())
+                }
+                None => (self.width, self.height),
+            };
+
+            let context = PassContext {
+                gl,
+                programs,
+                max_texture_units,
+                output: target,
+                width,
+                height,
+                fullscreen,
+            };
+            pass.render(&context, &input)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The buffers the pass `index` of `count` enabled passes reads and writes,
+/// `None` for the scene target and the output.
+fn pass_buffers(index: usize, count: usize) -> (Option<usize>, Option<usize>) {
+    let read = index.checked_sub(1).map(|previous| previous % 2);
+    let write = if index + 1 == count { None } else { Some(index % 2) };
+    (read, write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::passes::{color_grading, fxaa, tone_mapping, vignette, ColorGrading, ToneMapping};
+    use super::*;
+    use crate::core::preprocessor::{preprocess, ShaderChunks};
+
+    use std::cell::Cell;
+
+    /// Records what the composer tells it.
+    struct TestPass {
+        enabled: bool,
+        size: Rc<Cell<(u32, u32)>>,
+        format: Rc<Cell<Option<TextureFormat>>>,
+    }
+
+    impl Pass for TestPass {
+        fn render(&mut self, _context: &PassContext, _input: &Rc<RefCell<Texture>>) -> Result<(), JsValue> {
+            Ok(())
+        }
+
+        fn set_size(&mut self, width: u32, height: u32) {
+            self.size.set((width, height));
+        }
+
+        fn set_format(&mut self, format: TextureFormat) {
+            self.format.set(Some(format));
+        }
+
+        fn is_enabled(&self) -> bool {
+            self.enabled
+        }
+    }
+
+    #[test]
+    fn test_pass_buffers() {
+        // a single pass goes from the scene to the output
+        assert_eq!(pass_buffers(0, 1), (None, None));
+
+        let buffers: Vec<_> = (0..4).map(|i| pass_buffers(i, 4)).collect();
+        assert_eq!(buffers, vec![(None, Some(0)), (Some(0), Some(1)), (Some(1), Some(0)), (Some(0), None)]);
+    }
+
+    #[test]
+    fn test_passes() {
+        let mut composer = Composer::new(TextureFormat::Rgba8, 1);
+        assert!(!composer.is_active());
+
+        let size = Rc::new(Cell::new((0, 0)));
+        let format = Rc::new(Cell::new(None));
+        composer.add_pass(TestPass {
+            enabled: false,
+            size: size.clone(),
+            format: format.clone(),
+        });
+        assert!(!composer.is_active());
+        assert_eq!(size.get(), (1, 1));
+        assert_eq!(format.get(), Some(TextureFormat::Rgba8));
+
+        // disabled passes are resized too, to be ready when enabled
+        composer.set_size(640, 0);
+        assert_eq!(size.get(), (640, 1));
+        assert_eq!(composer.scene_target().borrow().width(), 640);
+
+        composer.add_pass(TestPass {
+            enabled: true,
+            size: Rc::new(Cell::new((0, 0))),
+            format: Rc::new(Cell::new(None)),
+        });
+        assert!(composer.is_active());
+    }
+
+    #[test]
+    fn test_sources_preprocess() {
+        let chunks = ShaderChunks::new();
+        let passes = [
+            fxaa(),
+            tone_mapping(ToneMapping::Aces, 1.0, true),
+            vignette(0.5, 0.5),
+            color_grading(&ColorGrading::new()),
+        ];
+        for pass in passes.iter() {
+            let material = &pass.material;
+            for source in &[material.vertex_shader(), material.fragment_shader()] {
+                let output = preprocess(source, material.defines(), &chunks).unwrap();
+                assert!(output.starts_with("#version 300 es\n"));
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use cgmath::{vec3, Vector3};
+
+use super::ShaderPass;
+
+/// Fast approximate anti-aliasing, best run after tone mapping on colors in
+/// [0, 1].
+pub fn fxaa() -> ShaderPass {
+    ShaderPass::new(include_str!("../shaders/fxaa_fragment.glsl"))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMapping {
+    /// Clamps to [0, 1].
+    Linear,
+    Reinhard,
+    /// Filmic curve fitted to ACES.
+    Aces,
+}
+
+/// Maps the HDR scene to [0, 1], usually the last pass working on linear
+/// colors. `exposure` scales the colors first.
+///
+/// With `encode_srgb` the result is encoded for display, for scenes drawn with
+/// linear output such as `PbrMaterial::linear_output` and
+/// `PhongMaterial::linear_output`. Without it the colors
+/// are left as is, for materials already writing display colors.
+pub fn tone_mapping(mapping: ToneMapping, exposure: f32, encode_srgb: bool) -> ShaderPass {
+    let mut pass = ShaderPass::new(include_str!("../shaders/tone_mapping_fragment.glsl"));
+    match mapping {
+        ToneMapping::Linear => {}
+        ToneMapping::Reinhard => pass.material.set_define("TONE_MAPPING_REINHARD", ""),
+        ToneMapping::Aces => pass.material.set_define("TONE_MAPPING_ACES", ""),
+    }
+    pass.material.toggle_define("ENCODE_SRGB", encode_srgb);
+    pass.material.set_uniform("exposure", exposure);
+    pass
+}
+
+/// Darkens the corners from `offset`, a distance to the center where 1 is a
+/// corner, up to `darkness` in the corners.
+pub fn vignette(offset: f32, darkness: f32) -> ShaderPass {
+    let mut pass = ShaderPass::new(include_str!("../shaders/vignette_fragment.glsl"));
+    pass.material.set_uniform("offset", offset);
+    pass.material.set_uniform("darkness", darkness);
+    pass
+}
+
+/// Color adjustments, the neutral values are the defaults of `new`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorGrading {
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Scales the distance to mid gray.
+    pub contrast: f32,
+    /// 0 is grayscale, above 1 oversaturates.
+    pub saturation: f32,
+    /// Multiplies every color.
+    pub tint: Vector3<f32>,
+}
+
+impl Default for ColorGrading {
+    fn default() -> ColorGrading {
+        ColorGrading::new()
+    }
+}
+
+impl ColorGrading {
+    pub fn new() -> ColorGrading {
+        ColorGrading {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            tint: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Updates a pass created by `color_grading`.
+    pub fn apply(&self, pass: &mut ShaderPass) {
+        pass.material.set_uniform("brightness", self.brightness);
+        pass.material.set_uniform("contrast", self.contrast);
+        pass.material.set_uniform("saturation", self.saturation);
+        pass.material.set_uniform("tint", self.tint);
+    }
+}
+
+pub fn color_grading(grading: &ColorGrading) -> ShaderPass {
+    let mut pass = ShaderPass::new(include_str!("../shaders/color_grading_fragment.glsl"));
+    grading.apply(&mut pass);
+    pass
+}
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Named GLSL snippets available to `#include "name"`.
+pub struct ShaderChunks {
+    chunks: HashMap<String, String>,
+}
+
+impl Default for ShaderChunks {
+    fn default() -> ShaderChunks {
+        ShaderChunks::new()
+    }
+}
+
+impl ShaderChunks {
+    /// A registry holding the chunks from `core/shaders/chunks`.
+    pub fn new() -> ShaderChunks {
+        let mut chunks = ShaderChunks::empty();
+        chunks.register("common", include_str!("shaders/chunks/common.glsl"));
+        chunks.register("lighting", include_str!("shaders/chunks/lighting.glsl"));
+        chunks.register("color", include_str!("shaders/chunks/color.glsl"));
+        chunks
+    }
+
+    pub fn empty() -> ShaderChunks {
+        ShaderChunks { chunks: HashMap::new() }
+    }
+
+    /// Adds or replaces a chunk.
+    pub fn register(&mut self, name: &str, source: &str) {
+        self.chunks.insert(name.to_string(), source.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.chunks.get(name).map(|s| s.as_str())
+    }
+}
+
+/// Where a line of preprocessed source comes from.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LineOrigin {
+    /// 1-based line of the shader source itself.
+    Source(usize),
+    /// 1-based line of an included chunk.
+    Chunk(String, usize),
+    /// A `#define` added by the preprocessor.
+    Define,
+}
+
+impl std::fmt::Display for LineOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineOrigin::Source(line) => write!(f, "line {}", line),
+            LineOrigin::Chunk(name, line) => write!(f, "chunk \"{}\" line {}", name, line),
+            LineOrigin::Define => write!(f, "injected define"),
+        }
+    }
+}
+
+/// Preprocessed source, with the origin of each of its lines.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    pub lines: Vec<LineOrigin>,
+    /// The chunks included, directly or not.
+    pub includes: Vec<String>,
+}
+
+impl Preprocessed {
+    /// Origin of the 1-based `line` of the preprocessed source.
+    pub fn origin(&self, line: usize) -> Option<&LineOrigin> {
+        line.checked_sub(1).and_then(|i| self.lines.get(i))
+    }
+}
+
+/// Expands the includes of `source`, then puts its `#version` directive on
+/// the first line followed by one `#define` per entry of `defines`. An empty
+/// value defines a flag such as `#define USE_TEXTURE`.
+pub fn preprocess(source: &str, defines: &BTreeMap<String, String>, chunks: &ShaderChunks) -> Result<String, String> {
+    preprocess_with_origins(source, defines, chunks).map(|p| p.source)
+}
+
+/// Same as `preprocess`, keeping track of where each line comes from to
+/// report compile errors against the original sources.
+pub fn preprocess_with_origins(
+    source: &str,
+    defines: &BTreeMap<String, String>,
+    chunks: &ShaderChunks,
+) -> Result<Preprocessed, String> {
+    let mut version = None;
+    let mut body = Preprocessed {
+        source: String::new(),
+        lines: Vec::new(),
+        includes: Vec::new(),
+    };
+    expand(source, None, chunks, &mut Vec::new(), &mut version, &mut body)?;
+
+    let mut output = Preprocessed {
+        source: String::new(),
+        lines: Vec::new(),
+        includes: body.includes,
+    };
+    if let Some((version, origin)) = version {
+        push_line(&mut output, &version, origin);
+    }
+    for (name, value) in defines {
+        if value.is_empty() {
+            push_line(&mut output, &format!("#define {}", name), LineOrigin::Define);
+        } else {
+            push_line(&mut output, &format!("#define {} {}", name, value), LineOrigin::Define);
+        }
+    }
+    output.source.push_str(&body.source);
+    output.lines.extend(body.lines);
+
+    Ok(output)
+}
+
+fn push_line(output: &mut Preprocessed, line: &str, origin: LineOrigin) {
+    output.source.push_str(line);
+    output.source.push('\n');
+    output.lines.push(origin);
+}
+
+fn expand(
+    source: &str,
+    chunk: Option<&str>,
+    chunks: &ShaderChunks,
+    stack: &mut Vec<String>,
+    version: &mut Option<(String, LineOrigin)>,
+    output: &mut Preprocessed,
+) -> Result<(), String> {
+    for (index, line) in source.lines().enumerate() {
+        let directive = line.trim();
+        let origin = match chunk {
+            Some(name) => LineOrigin::Chunk(name.to_string(), index + 1),
+            None => LineOrigin::Source(index + 1),
+        };
+
+        if directive.starts_with("#version") {
+            match version {
+                Some((v, _)) if v != directive => {
+                    return Err(format!("Conflicting '{}' and '{}' directives", v, directive));
+                }
+                Some(_) => {}
+                None => *version = Some((directive.to_string(), origin)),
+            }
+            continue;
+        }
+
+        if let Some(name) = parse_include(directive) {
+            if stack.iter().any(|n| n == name) {
+                return Err(format!("Recursive include of chunk '{}' ({} -> {})", name, stack.join(" -> "), name));
+            }
+            let chunk = chunks
+                .get(name)
+                .ok_or_else(|| format!("Unknown shader chunk '{}'", name))?;
+
+            if !output.includes.iter().any(|n| n == name) {
+                output.includes.push(name.to_string());
+            }
+            stack.push(name.to_string());
+            expand(chunk, Some(name), chunks, stack, version, output)?;
+            stack.pop();
+            continue;
+        }
+
+        push_line(output, line, origin);
+    }
+
+    Ok(())
+}
+
+/// `#include "name"` or `#include <name>`.
+fn parse_include(directive: &str) -> Option<&str> {
+    let rest = directive.strip_prefix("#include")?.trim();
+    let name = rest
+        .strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('<').and_then(|r| r.strip_suffix('>')))?;
+    Some(name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks() -> ShaderChunks {
+        let mut chunks = ShaderChunks::empty();
+        chunks.register("a", "float a() { return 1.0; }");
+        chunks.register("b", "#include \"a\"\nfloat b() { return a(); }");
+        chunks.register("loop", "#include <loop>");
+        chunks
+    }
+
+    #[test]
+    fn test_includes_and_defines() {
+        let mut defines = BTreeMap::new();
+        defines.insert("NUM_LIGHTS".to_string(), "4".to_string());
+        defines.insert("USE_TEXTURE".to_string(), "".to_string());
+
+        let source = "// header\n#version 300 es\n#include \"b\"\nvoid main(void) {}\n";
+        let output = preprocess(source, &defines, &chunks()).unwrap();
+        assert_eq!(
+            output,
+            "#version 300 es\n#define NUM_LIGHTS 4\n#define USE_TEXTURE\n// header\n\
+             float a() { return 1.0; }\nfloat b() { return a(); }\nvoid main(void) {}\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let chunks = chunks();
+        let defines = BTreeMap::new();
+        assert!(preprocess("#include \"missing\"", &defines, &chunks).is_err());
+        assert!(preprocess("#include \"loop\"", &defines, &chunks).is_err());
+        assert!(preprocess("#version 300 es\n#version 100\n", &defines, &chunks).is_err());
+    }
+
+    #[test]
+    fn test_origins() {
+        let mut defines = BTreeMap::new();
+        defines.insert("A".to_string(), "1".to_string());
+
+        let source = "#version 300 es\n#include \"b\"\nvoid main(void) {}";
+        let output = preprocess_with_origins(source, &defines, &chunks()).unwrap();
+        assert_eq!(
+            output.lines,
+            vec![
+                LineOrigin::Source(1),
+                LineOrigin::Define,
+                LineOrigin::Chunk("a".to_string(), 1),
+                LineOrigin::Chunk("b".to_string(), 2),
+                LineOrigin::Source(3),
+            ]
+        );
+        assert_eq!(output.origin(4), Some(&LineOrigin::Chunk("b".to_string(), 2)));
+        assert_eq!(output.includes, vec!["b".to_string(), "a".to_string()]);
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    rc::Rc,
+};
+
+use web_sys::{WebGl2RenderingContext as GL, WebGlProgram, WebGlUniformLocation};
+
+use super::{
+    material::{compile_preprocessed_shader, link_program},
+    preprocessor::{preprocess_with_origins, ShaderChunks},
+    shader_error::{ShaderError, ShaderStage},
+};
+
+/// An active attribute or uniform of a linked program.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ActiveVariable {
+    /// As reported by the driver, arrays are named `name[0]`.
+    pub name: String,
+    /// GL type, e.g. `FLOAT_VEC3`.
+    pub kind: u32,
+    /// Number of array elements, 1 when not an array.
+    pub size: i32,
+    /// Attribute location, -1 for uniforms.
+    pub location: i32,
+}
+
+impl ActiveVariable {
+    /// True if `name` refers to this variable, `name` or `name[0]` for arrays.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || (self.name.ends_with("[0]") && &self.name[..self.name.len() - 3] == name)
+    }
+}
+
+/// A linked program with its active attributes and uniforms.
+pub struct ShaderProgram {
+    pub program: WebGlProgram,
+    pub attributes: Vec<ActiveVariable>,
+    pub uniforms: Vec<ActiveVariable>,
+    /// The chunks included by its shaders, when built by a `ProgramCache`.
+    pub includes: Vec<String>,
+
+    // None when the program has no such uniform
+    uniform_locations: RefCell<HashMap<String, Option<WebGlUniformLocation>>>,
+}
+
+impl ShaderProgram {
+    /// Queries the active variables of a successfully linked program.
+    pub fn new(gl: &GL, program: WebGlProgram) -> ShaderProgram {
+        let count = |pname| gl.get_program_parameter(&program, pname).as_f64().unwrap_or(0.0) as u32;
+
+        let attributes = (0..count(GL::ACTIVE_ATTRIBUTES))
+            .filter_map(|i| gl.get_active_attrib(&program, i))
+            .map(|info| ActiveVariable {
+                location: gl.get_attrib_location(&program, &info.name()),
+                name: info.name(),
+                kind: info.type_(),
+                size: info.size(),
+            })
+            .collect();
+
+        let uniforms = (0..count(GL::ACTIVE_UNIFORMS))
+            .filter_map(|i| gl.get_active_uniform(&program, i))
+            .map(|info| ActiveVariable {
+                name: info.name(),
+                kind: info.type_(),
+                size: info.size(),
+                location: -1,
+            })
+            .collect();
+
+        ShaderProgram {
+            program,
+            attributes,
+            uniforms,
+            includes: Vec::new(),
+            uniform_locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Looked up once, then cached.
+    pub fn uniform_location(&self, gl: &GL, name: &str) -> Option<WebGlUniformLocation> {
+        self.uniform_locations
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| gl.get_uniform_location(&self.program, name))
+            .clone()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&ActiveVariable> {
+        self.attributes.iter().find(|a| a.is_named(name))
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&ActiveVariable> {
+        self.uniforms.iter().find(|u| u.is_named(name))
+    }
+}
+
+/// Identifies a program by its sources and the defines injected in them.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ProgramKey {
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+    pub defines: BTreeMap<String, String>,
+}
+
+/// Values built once per key and shared, with failures remembered. The
+/// bookkeeping of `ProgramCache`, apart from GL.
+struct SharedCache<K, V, E> {
+    values: HashMap<K, Rc<V>>,
+    // kept so that a broken value is not rebuilt every frame
+    failures: HashMap<K, E>,
+    // bumped by `invalidate`, holders of a value from an older generation
+    // request it again
+    generation: u64,
+    // values dropped from the cache that may still be in use
+    retired: Vec<Rc<V>>,
+}
+
+impl<K: Eq + Hash + Clone, V, E: Clone> SharedCache<K, V, E> {
+    fn new() -> SharedCache<K, V, E> {
+        SharedCache {
+            values: HashMap::new(),
+            failures: HashMap::new(),
+            generation: 0,
+            retired: Vec::new(),
+        }
+    }
+
+    fn get<F: FnOnce() -> Result<V, E>>(&mut self, key: &K, build: F) -> Result<Rc<V>, E> {
+        if let Some(value) = self.values.get(key) {
+            return Ok(value.clone());
+        }
+        if let Some(error) = self.failures.get(key) {
+            return Err(error.clone());
+        }
+
+        match build() {
+            Ok(value) => {
+                let value = Rc::new(value);
+                self.values.insert(key.clone(), value.clone());
+                Ok(value)
+            }
+            Err(error) => {
+                self.failures.insert(key.clone(), error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// The values matching `stale` and the failures are rebuilt on their
+    /// next `get`.
+    fn invalidate<F: Fn(&V) -> bool>(&mut self, stale: F) {
+        let keys: Vec<K> = self
+            .values
+            .iter()
+            .filter(|(_, value)| stale(value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        if keys.is_empty() && self.failures.is_empty() {
+            return;
+        }
+
+        self.generation += 1;
+        for key in keys {
+            self.retired.extend(self.values.remove(&key));
+        }
+        self.failures.clear();
+    }
+
+    /// Drops the values only referenced by the cache, passing them to `release`.
+    fn collect<F: FnMut(&V)>(&mut self, mut release: F) {
+        let mut keep = |value: &Rc<V>| {
+            let used = Rc::strong_count(value) > 1;
+            if !used {
+                release(value);
+            }
+            used
+        };
+        self.values.retain(|_, value| keep(value));
+        self.retired.retain(keep);
+    }
+}
+
+/// Programs shared by every material with the same sources and defines. A
+/// program is deleted by `collect` once no material holds it anymore.
+pub struct ProgramCache {
+    /// Chunks available to `#include` in the shaders of every material,
+    /// replace them with `register_chunk` once programs are built.
+    pub chunks: ShaderChunks,
+
+    programs: SharedCache<ProgramKey, ShaderProgram, ShaderError>,
+}
+
+impl Default for ProgramCache {
+    fn default() -> ProgramCache {
+        ProgramCache::new()
+    }
+}
+
+impl ProgramCache {
+    pub fn new() -> ProgramCache {
+        ProgramCache {
+            chunks: ShaderChunks::new(),
+            programs: SharedCache::new(),
+        }
+    }
+
+    /// Bumped when the chunks change, materials holding a program from an
+    /// older generation request it again.
+    pub fn generation(&self) -> u64 {
+        self.programs.generation
+    }
+
+    /// Adds or replaces a chunk, the programs built so far are rebuilt on
+    /// their next use.
+    pub fn register_chunk(&mut self, name: &str, source: &str) {
+        if self.chunks.get(name) == Some(source) {
+            return;
+        }
+        self.chunks.register(name, source);
+        self.programs.invalidate(|program| program.includes.iter().any(|n| n == name));
+    }
+
+    pub fn get(&mut self, gl: &GL, key: &ProgramKey) -> Result<Rc<ShaderProgram>, ShaderError> {
+        let chunks = &self.chunks;
+        self.programs.get(key, || build(gl, chunks, key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.values.is_empty()
+    }
+
+    /// Deletes the programs only referenced by the cache.
+    pub fn collect(&mut self, gl: &GL) {
+        self.programs.collect(|program| gl.delete_program(Some(&program.program)));
+    }
+}
+
+fn build(gl: &GL, chunks: &ShaderChunks, key: &ProgramKey) -> Result<ShaderProgram, ShaderError> {
+    let vertex_source = preprocess_with_origins(&key.vertex_shader, &key.defines, chunks).map_err(|message| {
+        ShaderError::Preprocess {
+            stage: ShaderStage::Vertex,
+            message,
+        }
+    })?;
+    let fragment_source = preprocess_with_origins(&key.fragment_shader, &key.defines, chunks).map_err(|message| {
+        ShaderError::Preprocess {
+            stage: ShaderStage::Fragment,
+            message,
+        }
+    })?;
+
+    let vert_shader = compile_preprocessed_shader(gl, GL::VERTEX_SHADER, &vertex_source)?;
+    let frag_shader = match compile_preprocessed_shader(gl, GL::FRAGMENT_SHADER, &fragment_source) {
+        Ok(shader) => shader,
+        Err(error) => {
+            gl.delete_shader(Some(&vert_shader));
+            return Err(error);
+        }
+    };
+    let linked = link_program(gl, &vert_shader, &frag_shader);
+
+    // the program keeps what it needs once linked
+    gl.delete_shader(Some(&vert_shader));
+    gl.delete_shader(Some(&frag_shader));
+
+    let mut program = ShaderProgram::new(gl, linked?);
+    program.includes = vertex_source.includes;
+    for name in fragment_source.includes {
+        if !program.includes.contains(&name) {
+            program.includes.push(name);
+        }
+    }
+    Ok(program)
+}
+
+/// GLSL name of a GL type, for diagnostics.
+pub fn type_name(kind: u32) -> &'static str {
+    match kind {
+        GL::FLOAT => "float",
+        GL::FLOAT_VEC2 => "vec2",
+        GL::FLOAT_VEC3 => "vec3",
+        GL::FLOAT_VEC4 => "vec4",
+        GL::INT => "int",
+        GL::INT_VEC2 => "ivec2",
+        GL::INT_VEC3 => "ivec3",
+        GL::INT_VEC4 => "ivec4",
+        GL::UNSIGNED_INT => "uint",
+        GL::BOOL => "bool",
+        GL::FLOAT_MAT2 => "mat2",
+        GL::FLOAT_MAT3 => "mat3",
+        GL::FLOAT_MAT4 => "mat4",
+        GL::SAMPLER_2D => "sampler2D",
+        GL::SAMPLER_CUBE => "samplerCube",
+        GL::SAMPLER_3D => "sampler3D",
+        GL::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        GL::SAMPLER_2D_ARRAY => "sampler2DArray",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn build_count(builds: &Cell<u32>, result: Result<u32, String>) -> impl FnOnce() -> Result<u32, String> + '_ {
+        move || {
+            builds.set(builds.get() + 1);
+            result
+        }
+    }
+
+    #[test]
+    fn test_shared_cache_get() {
+        let builds = Cell::new(0);
+        let mut cache = SharedCache::new();
+
+        let a = cache.get(&"a", build_count(&builds, Ok(1))).unwrap();
+        let b = cache.get(&"a", build_count(&builds, Ok(2))).unwrap();
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(*b, 1);
+        assert_eq!(builds.get(), 1);
+
+        // failures are remembered until invalidated
+        assert!(cache.get(&"broken", build_count(&builds, Err("error".to_string()))).is_err());
+        assert!(cache.get(&"broken", build_count(&builds, Ok(3))).is_err());
+        assert_eq!(builds.get(), 2);
+
+        cache.invalidate(|_| true);
+        assert_eq!(cache.generation, 1);
+        assert_eq!(*cache.get(&"broken", build_count(&builds, Ok(3))).unwrap(), 3);
+        assert_eq!(*cache.get(&"a", build_count(&builds, Ok(4))).unwrap(), 4);
+        assert_eq!(builds.get(), 4);
+    }
+
+    #[test]
+    fn test_shared_cache_invalidate() {
+        let mut cache: SharedCache<&str, u32, String> = SharedCache::new();
+        cache.invalidate(|_| true);
+        assert_eq!(cache.generation, 0);
+
+        let even = cache.get(&"even", || Ok(2)).unwrap();
+        let odd = cache.get(&"odd", || Ok(1)).unwrap();
+        cache.invalidate(|value| value % 2 == 0);
+        assert_eq!(cache.generation, 1);
+        assert!(Rc::ptr_eq(&cache.get(&"odd", || Ok(3)).unwrap(), &odd));
+        assert!(!Rc::ptr_eq(&cache.get(&"even", || Ok(4)).unwrap(), &even));
+
+        // nothing stale, nothing to request again
+        cache.invalidate(|value| *value > 10);
+        assert_eq!(cache.generation, 1);
+    }
+
+    #[test]
+    fn test_shared_cache_collect() {
+        let mut cache: SharedCache<&str, u32, String> = SharedCache::new();
+        let held = cache.get(&"held", || Ok(1)).unwrap();
+        cache.get(&"dropped", || Ok(2)).unwrap();
+
+        let mut released = Vec::new();
+        cache.collect(|value| released.push(*value));
+        assert_eq!(released, vec![2]);
+        assert_eq!(cache.values.len(), 1);
+
+        // retired values are kept while in use
+        cache.invalidate(|_| true);
+        cache.collect(|value| released.push(*value));
+        assert_eq!(released, vec![2]);
+        drop(held);
+        cache.collect(|value| released.push(*value));
+        assert_eq!(released, vec![2, 1]);
+        assert!(cache.retired.is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use cgmath::{Matrix4, Point3, Transform as _};
+
+use super::{graph::Node, object3d::Object3d};
+
+/// An object to draw this frame.
+pub struct RenderItem {
+    pub node: Rc<Node<Object3d>>,
+    pub render_order: i32,
+    /// Distance along the view direction, larger is farther.
+    pub depth: f32,
+}
+
+/// The renderable objects of a scene in drawing order. The render order of
+/// the objects comes first, across both queues. Within a render order, opaque
+/// objects are drawn front to back, to save shading hidden fragments, then
+/// transparent objects back to front so that they blend over what is behind
+/// them.
+pub struct RenderQueue {
+    pub opaque: Vec<RenderItem>,
+    pub transparent: Vec<RenderItem>,
+}
+
+impl Default for RenderQueue {
+    fn default() -> RenderQueue {
+        RenderQueue::new()
+    }
+}
+
+impl RenderQueue {
+    pub fn new() -> RenderQueue {
+        RenderQueue {
+            opaque: Vec::new(),
+            transparent: Vec::new(),
+        }
+    }
+
+    /// Gathers and sorts the objects below `root`, whose world matrices must
+    /// be up to date, as seen through `view_matrix`.
+    pub fn collect(root: &Rc<Node<Object3d>>, view_matrix: &Matrix4<f32>) -> RenderQueue {
+        let queue = RefCell::new(RenderQueue::new());
+        Node::traverse(root, &|node: &Rc<Node<Object3d>>| {
+            let n = node.value.borrow();
+            if let Some(renderer) = &n.renderer {
+                let position = Point3::from_homogeneous(n.transform.matrix_world.w);
+                let item = RenderItem {
+                    node: node.clone(),
+                    render_order: n.render_order,
+                    depth: -view_matrix.transform_point(position).z,
+                };
+                queue.borrow_mut().push(item, renderer.borrow().is_transparent());
+            }
+        });
+
+        let mut queue = queue.into_inner();
+        queue.sort();
+        queue
+    }
+
+    pub fn push(&mut self, item: RenderItem, transparent: bool) {
+        if transparent {
+            self.transparent.push(item);
+        } else {
+            self.opaque.push(item);
+        }
+    }
+
+    /// The sort is stable, objects in the same order at the same depth keep
+    /// their traversal order.
+    pub fn sort(&mut self) {
+        self.opaque
+            .sort_by(|a, b| a.render_order.cmp(&b.render_order).then(compare(a.depth, b.depth)));
+        self.transparent
+            .sort_by(|a, b| a.render_order.cmp(&b.render_order).then(compare(b.depth, a.depth)));
+    }
+
+    /// The items in drawing order, the queues must be sorted.
+    pub fn iter(&self) -> impl Iterator<Item = &RenderItem> {
+        let mut opaque = self.opaque.iter().peekable();
+        let mut transparent = self.transparent.iter().peekable();
+        std::iter::from_fn(move || match (opaque.peek(), transparent.peek()) {
+            (Some(a), Some(b)) if b.render_order < a.render_order => transparent.next(),
+            (Some(_), _) => opaque.next(),
+            (None, _) => transparent.next(),
+        })
+    }
+}
+
+fn compare(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        object3d::{Renderable, Transform},
+        renderer::RenderingContext,
+    };
+    use cgmath::vec3;
+    use wasm_bindgen::JsValue;
+
+    fn item(name: &str, render_order: i32, depth: f32) -> RenderItem {
+        let mut object = Object3d::new();
+        object.name = Some(name.to_string());
+        RenderItem {
+            node: Node::new_rc(object),
+            render_order,
+            depth,
+        }
+    }
+
+    fn names(items: &[RenderItem]) -> Vec<String> {
+        items.iter().map(|i| i.node.value.borrow().name.clone().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut queue = RenderQueue::new();
+        queue.push(item("far", 0, 10.0), false);
+        queue.push(item("near", 0, 1.0), false);
+        queue.push(item("last", 1, 0.5), false);
+        queue.push(item("glass far", 0, 8.0), true);
+        queue.push(item("glass near", 0, 2.0), true);
+        queue.push(item("overlay", -1, 1.0), true);
+        queue.sort();
+
+        assert_eq!(names(&queue.opaque), vec!["near", "far", "last"]);
+        assert_eq!(names(&queue.transparent), vec!["overlay", "glass far", "glass near"]);
+
+        // the render order applies across the queues
+        let order: Vec<String> = queue.iter().map(|i| i.node.value.borrow().name.clone().unwrap()).collect();
+        assert_eq!(order, vec!["overlay", "near", "far", "glass far", "glass near", "last"]);
+    }
+
+    struct Dummy(bool);
+
+    impl Renderable for Dummy {
+        fn render(&mut self, _: &Transform, _: &RenderingContext) -> Result<(), JsValue> {
+            Ok(())
+        }
+
+        fn is_transparent(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_collect() {
+        let root = Node::new_rc(Object3d::new());
+        for (z, transparent) in [(-4.0, false), (-2.0, true)].iter() {
+            let node = Node::new_rc(Object3d::new());
+            node.value.borrow_mut().transform.position = vec3(0.0, 0.0, *z);
+            node.value.borrow_mut().renderer = Some(RefCell::new(Box::new(Dummy(*transparent))));
+            Node::add_child(&root, &node);
+        }
+        Node::traverse(&root, &|n: &Rc<Node<Object3d>>| n.value.borrow_mut().update(n));
+
+        // camera at z = 1
+        let view = Matrix4::from_translation(vec3(0.0, 0.0, -1.0));
+        let queue = RenderQueue::collect(&root, &view);
+        assert_eq!(queue.opaque.len(), 1);
+        assert_eq!(queue.opaque[0].depth, 5.0);
+        assert_eq!(queue.transparent.len(), 1);
+        assert_eq!(queue.transparent[0].depth, 3.0);
+    }
+}
@@ -0,0 +1,326 @@
+use web_sys::WebGl2RenderingContext as GL;
+
+use super::material::BlendMode;
+
+/// Faces of the triangles drawn, front faces are counter-clockwise.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Front,
+    Back,
+    Double,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    fn gl(self) -> u32 {
+        match self {
+            CompareFunc::Never => GL::NEVER,
+            CompareFunc::Less => GL::LESS,
+            CompareFunc::Equal => GL::EQUAL,
+            CompareFunc::LessEqual => GL::LEQUAL,
+            CompareFunc::Greater => GL::GREATER,
+            CompareFunc::NotEqual => GL::NOTEQUAL,
+            CompareFunc::GreaterEqual => GL::GEQUAL,
+            CompareFunc::Always => GL::ALWAYS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    fn gl(self) -> u32 {
+        match self {
+            StencilOp::Keep => GL::KEEP,
+            StencilOp::Zero => GL::ZERO,
+            StencilOp::Replace => GL::REPLACE,
+            StencilOp::Increment => GL::INCR,
+            StencilOp::IncrementWrap => GL::INCR_WRAP,
+            StencilOp::Decrement => GL::DECR,
+            StencilOp::DecrementWrap => GL::DECR_WRAP,
+            StencilOp::Invert => GL::INVERT,
+        }
+    }
+}
+
+/// Stencil test and update, the same for front and back faces.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StencilState {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    /// When the stencil test fails.
+    pub fail: StencilOp,
+    /// When the stencil test passes and the depth test fails.
+    pub depth_fail: StencilOp,
+    /// When both tests pass.
+    pub pass: StencilOp,
+}
+
+impl Default for StencilState {
+    fn default() -> StencilState {
+        StencilState::new()
+    }
+}
+
+impl StencilState {
+    /// Passes everywhere and writes nothing.
+    pub fn new() -> StencilState {
+        StencilState {
+            func: CompareFunc::Always,
+            reference: 0,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// Slope scaled depth offset, against z-fighting of coplanar surfaces such
+/// as decals.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PolygonOffset {
+    pub factor: f32,
+    pub units: f32,
+}
+
+/// Fixed function state a material is drawn with, the defaults of `new` are
+/// also what the renderer restores outside of materials.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderState {
+    pub side: Side,
+    pub depth_test: bool,
+    /// `None` follows the blend mode, transparent materials do not write depth.
+    pub depth_write: Option<bool>,
+    pub depth_func: CompareFunc,
+    /// Red, green, blue and alpha.
+    pub color_mask: [bool; 4],
+    pub polygon_offset: Option<PolygonOffset>,
+    /// `None` disables the stencil test.
+    pub stencil: Option<StencilState>,
+}
+
+impl Default for RenderState {
+    fn default() -> RenderState {
+        RenderState::new()
+    }
+}
+
+impl RenderState {
+    pub fn new() -> RenderState {
+        RenderState {
+            side: Side::Front,
+            depth_test: true,
+            depth_write: None,
+            depth_func: CompareFunc::LessEqual,
+            color_mask: [true; 4],
+            polygon_offset: None,
+            stencil: None,
+        }
+    }
+}
+
+/// Blend factors for source and destination color, then alpha.
+type BlendFuncs = (u32, u32, u32, u32);
+
+fn blend_funcs(mode: BlendMode) -> Option<BlendFuncs> {
+    match mode {
+        BlendMode::Opaque | BlendMode::AlphaTest(_) => None,
+        BlendMode::AlphaBlend => Some((GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ONE, GL::ONE_MINUS_SRC_ALPHA)),
+        BlendMode::Additive => Some((GL::SRC_ALPHA, GL::ONE, GL::SRC_ALPHA, GL::ONE)),
+        BlendMode::Premultiplied => Some((GL::ONE, GL::ONE_MINUS_SRC_ALPHA, GL::ONE, GL::ONE_MINUS_SRC_ALPHA)),
+    }
+}
+
+/// Stores `value` in `cached` and tells whether it changed.
+fn update<T: PartialEq>(cached: &mut Option<T>, value: T) -> bool {
+    if cached.as_ref() == Some(&value) {
+        return false;
+    }
+    *cached = Some(value);
+    true
+}
+
+/// The GL state last set through it, so that redundant calls are skipped.
+/// Code changing the same state directly must restore the defaults of
+/// `RenderState::new` with an opaque blend mode, or call `invalidate`.
+pub struct StateCache {
+    // None until first set
+    cull_face: Option<Option<u32>>,
+    depth_test: Option<bool>,
+    depth_mask: Option<bool>,
+    depth_func: Option<u32>,
+    color_mask: Option<[bool; 4]>,
+    polygon_offset: Option<Option<(f32, f32)>>,
+    stencil: Option<Option<StencilState>>,
+    stencil_mask: Option<u32>,
+    blend: Option<Option<BlendFuncs>>,
+}
+
+impl Default for StateCache {
+    fn default() -> StateCache {
+        StateCache::new()
+    }
+}
+
+impl StateCache {
+    pub fn new() -> StateCache {
+        StateCache {
+            cull_face: None,
+            depth_test: None,
+            depth_mask: None,
+            depth_func: None,
+            color_mask: None,
+            polygon_offset: None,
+            stencil: None,
+            stencil_mask: None,
+            blend: None,
+        }
+    }
+
+    /// Forgets the known state, the next `apply` sets everything.
+    pub fn invalidate(&mut self) {
+        *self = StateCache::new();
+    }
+
+    /// Sets the default state whatever the known state.
+    pub fn reset(&mut self, gl: &GL) {
+        self.invalidate();
+        self.apply(gl, &RenderState::new(), BlendMode::Opaque);
+    }
+
+    pub fn apply(&mut self, gl: &GL, state: &RenderState, blend_mode: BlendMode) {
+        let cull_face = match state.side {
+            Side::Front => Some(GL::BACK),
+            Side::Back => Some(GL::FRONT),
+            Side::Double => None,
+        };
+        if update(&mut self.cull_face, cull_face) {
+            match cull_face {
+                Some(face) => {
+                    gl.enable(GL::CULL_FACE);
+                    gl.cull_face(face);
+                }
+                None => gl.disable(GL::CULL_FACE),
+            }
+        }
+
+        if update(&mut self.depth_test, state.depth_test) {
+            enable(gl, GL::DEPTH_TEST, state.depth_test);
+        }
+        let depth_write = state.depth_write.unwrap_or_else(|| !blend_mode.is_transparent());
+        if update(&mut self.depth_mask, depth_write) {
+            gl.depth_mask(depth_write);
+        }
+        if update(&mut self.depth_func, state.depth_func.gl()) {
+            gl.depth_func(state.depth_func.gl());
+        }
+
+        if update(&mut self.color_mask, state.color_mask) {
+            let [r, g, b, a] = state.color_mask;
+            gl.color_mask(r, g, b, a);
+        }
+
+        let offset = state.polygon_offset.map(|o| (o.factor, o.units));
+        if update(&mut self.polygon_offset, offset) {
+            enable(gl, GL::POLYGON_OFFSET_FILL, offset.is_some());
+            if let Some((factor, units)) = offset {
+                gl.polygon_offset(factor, units);
+            }
+        }
+
+        if update(&mut self.stencil, state.stencil) {
+            enable(gl, GL::STENCIL_TEST, state.stencil.is_some());
+            if let Some(stencil) = state.stencil {
+                gl.stencil_func(stencil.func.gl(), stencil.reference, stencil.read_mask);
+                gl.stencil_op(stencil.fail.gl(), stencil.depth_fail.gl(), stencil.pass.gl());
+            }
+        }
+        // also masks stencil clears, so it is restored without a stencil test
+        let stencil_mask = stencil_write_mask(state.stencil);
+        if update(&mut self.stencil_mask, stencil_mask) {
+            gl.stencil_mask(stencil_mask);
+        }
+
+        let blend = blend_funcs(blend_mode);
+        if update(&mut self.blend, blend) {
+            enable(gl, GL::BLEND, blend.is_some());
+            if let Some((src_rgb, dst_rgb, src_alpha, dst_alpha)) = blend {
+                gl.blend_equation(GL::FUNC_ADD);
+                gl.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            }
+        }
+    }
+}
+
+fn stencil_write_mask(stencil: Option<StencilState>) -> u32 {
+    stencil.map_or(0xff, |stencil| stencil.write_mask)
+}
+
+fn enable(gl: &GL, capability: u32, enabled: bool) {
+    if enabled {
+        gl.enable(capability);
+    } else {
+        gl.disable(capability);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut cached = None;
+        assert!(update(&mut cached, Some(GL::BACK)));
+        assert!(!update(&mut cached, Some(GL::BACK)));
+        assert!(update(&mut cached, None));
+        assert!(!update(&mut cached, None));
+        assert_eq!(cached, Some(None));
+    }
+
+    #[test]
+    fn test_stencil_write_mask() {
+        let mut stencil = StencilState::new();
+        stencil.write_mask = 0x0f;
+        assert_eq!(stencil_write_mask(Some(stencil)), 0x0f);
+        // the default is restored with the test disabled
+        assert_eq!(stencil_write_mask(None), 0xff);
+
+        let mut cached = None;
+        assert!(update(&mut cached, stencil_write_mask(Some(stencil))));
+        assert!(update(&mut cached, stencil_write_mask(None)));
+        assert_eq!(cached, Some(0xff));
+    }
+
+    #[test]
+    fn test_blend_funcs() {
+        assert_eq!(blend_funcs(BlendMode::AlphaTest(0.5)), None);
+        assert_eq!(blend_funcs(BlendMode::Premultiplied).unwrap().0, GL::ONE);
+        assert!(blend_funcs(BlendMode::Additive).is_some());
+    }
+}
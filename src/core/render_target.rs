@@ -0,0 +1,411 @@
+use std::{cell::RefCell, rc::Rc};
+
+use js_sys::Array;
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext as GL, WebGlFramebuffer, WebGlRenderbuffer};
+
+use super::texture::{Filter, Sampler, Texture, TextureFormat, TextureSource, Wrap};
+
+/// Attachments of a render target.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RenderTargetOptions {
+    /// One color texture per entry, written by `layout(location = i)`
+    /// fragment outputs.
+    pub colors: Vec<TextureFormat>,
+    /// Depth, or depth and stencil, buffer format.
+    pub depth: Option<TextureFormat>,
+    /// Keeps the depth buffer in a texture that can be sampled.
+    pub depth_texture: bool,
+    /// Samples per pixel, above 1 rendering goes to multisampled buffers
+    /// resolved into the textures by `resolve`.
+    pub samples: i32,
+    /// Makes the textures 2D arrays of this many layers, drawn one at a time
+    /// after `bind_layer`. Layered targets are not multisampled.
+    pub layers: Option<u32>,
+}
+
+impl Default for RenderTargetOptions {
+    fn default() -> RenderTargetOptions {
+        RenderTargetOptions::new()
+    }
+}
+
+impl RenderTargetOptions {
+    /// One RGBA8 color texture and a depth buffer.
+    pub fn new() -> RenderTargetOptions {
+        RenderTargetOptions {
+            colors: vec![TextureFormat::Rgba8],
+            depth: Some(TextureFormat::Depth24),
+            depth_texture: false,
+            samples: 1,
+            layers: None,
+        }
+    }
+}
+
+/// An offscreen framebuffer rendering into textures, created on first use.
+pub struct RenderTarget {
+    pub options: RenderTargetOptions,
+
+    width: u32,
+    height: u32,
+    colors: Vec<Rc<RefCell<Texture>>>,
+    depth: Option<Rc<RefCell<Texture>>>,
+    // the textures are attached to it, and it is drawn to without multisampling
+    framebuffer: Option<WebGlFramebuffer>,
+    multisample_framebuffer: Option<WebGlFramebuffer>,
+    renderbuffers: Vec<WebGlRenderbuffer>,
+    needs_update: bool,
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32, options: RenderTargetOptions) -> RenderTarget {
+        let texture = |format: TextureFormat| {
+            let mut texture = Texture::new(empty_source(width, height, options.layers, format));
+            texture.sampler = Sampler::new();
+            texture.sampler.wrap_s = Wrap::ClampToEdge;
+            texture.sampler.wrap_t = Wrap::ClampToEdge;
+            texture.sampler.mipmaps = false;
+            if !format.is_filterable() {
+                texture.sampler.min_filter = Filter::Nearest;
+                texture.sampler.mag_filter = Filter::Nearest;
+            }
+            Rc::new(RefCell::new(texture))
+        };
+
+        let colors = options.colors.iter().map(|format| texture(*format)).collect();
+        let depth = match options.depth {
+            Some(format) if options.depth_texture => Some(texture(format)),
+            _ => None,
+        };
+
+        RenderTarget {
+            options,
+            width,
+            height,
+            colors,
+            depth,
+            framebuffer: None,
+            multisample_framebuffer: None,
+            renderbuffers: Vec::new(),
+            needs_update: true,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Reallocates the attachments on next use, their content is lost.
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.update_sources();
+    }
+
+    pub fn layers(&self) -> Option<u32> {
+        self.options.layers
+    }
+
+    /// Changes the layer count of a layered target. Reallocates the
+    /// attachments on next use, their content is lost.
+    pub fn set_layers(&mut self, layers: u32) {
+        if self.options.layers.is_some() && self.options.layers != Some(layers) {
+            self.options.layers = Some(layers);
+            self.update_sources();
+        }
+    }
+
+    fn update_sources(&mut self) {
+        for texture in self.colors.iter().chain(self.depth.iter()) {
+            let mut texture = texture.borrow_mut();
+            let format = match texture.source {
+                TextureSource::Empty { format, .. } | TextureSource::EmptyArray { format, .. } => format,
+                _ => continue,
+            };
+            texture.source = empty_source(self.width, self.height, self.options.layers, format);
+            texture.set_needs_update();
+        }
+        self.needs_update = true;
+    }
+
+    /// The color texture of attachment `index`, usable in materials once
+    /// rendered to.
+    pub fn texture(&self, index: usize) -> &Rc<RefCell<Texture>> {
+        &self.colors[index]
+    }
+
+    pub fn depth_texture(&self) -> Option<&Rc<RefCell<Texture>>> {
+        self.depth.as_ref()
+    }
+
+    fn is_multisampled(&self) -> bool {
+        self.options.samples > 1
+    }
+
+    /// Binds the framebuffer to draw to, with a viewport covering it.
+    pub fn bind(&mut self, gl: &GL) -> Result<(), JsValue> {
+        if self.needs_update {
+            self.create(gl)?;
+            self.needs_update = false;
+        }
+
+        let framebuffer = if self.is_multisampled() {
+            &self.multisample_framebuffer
+        } else {
+            &self.framebuffer
+        };
+        gl.bind_framebuffer(GL::FRAMEBUFFER, framebuffer.as_ref());
+        gl.viewport(0, 0, self.width as i32, self.height as i32);
+        Ok(())
+    }
+
+    /// Binds the framebuffer with the layer `layer` of each texture attached,
+    /// for layered targets.
+    pub fn bind_layer(&mut self, gl: &GL, layer: u32) -> Result<(), JsValue> {
+        self.bind(gl)?;
+        for (i, texture) in self.colors.iter().enumerate() {
+            let attachment = GL::COLOR_ATTACHMENT0 + i as u32;
+            let mut texture = texture.borrow_mut();
+            gl.framebuffer_texture_layer(GL::FRAMEBUFFER, attachment, Some(texture.get_texture(gl)?), 0, layer as i32);
+        }
+        if let (Some(format), Some(texture)) = (self.options.depth, &self.depth) {
+            let mut texture = texture.borrow_mut();
+            let attachment = depth_attachment(format);
+            gl.framebuffer_texture_layer(GL::FRAMEBUFFER, attachment, Some(texture.get_texture(gl)?), 0, layer as i32);
+        }
+        Ok(())
+    }
+
+    /// Copies the multisampled buffers into the textures, nothing to do
+    /// without multisampling. Leaves no framebuffer bound.
+    pub fn resolve(&mut self, gl: &GL) {
+        if !self.is_multisampled() || self.needs_update {
+            return;
+        }
+
+        let (width, height) = (self.width as i32, self.height as i32);
+        gl.bind_framebuffer(GL::READ_FRAMEBUFFER, self.multisample_framebuffer.as_ref());
+        gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, self.framebuffer.as_ref());
+
+        // one blit per attachment, each reading and writing a single buffer
+        for i in 0..self.colors.len() as u32 {
+            let attachment = GL::COLOR_ATTACHMENT0 + i;
+            gl.read_buffer(attachment);
+            gl.draw_buffers(&draw_buffers(self.colors.len(), Some(i)));
+            gl.blit_framebuffer(0, 0, width, height, 0, 0, width, height, GL::COLOR_BUFFER_BIT, GL::NEAREST);
+        }
+        if let (Some(format), Some(_)) = (self.options.depth, &self.depth) {
+            let mut mask = GL::DEPTH_BUFFER_BIT;
+            if format.has_stencil() {
+                mask |= GL::STENCIL_BUFFER_BIT;
+            }
+            gl.blit_framebuffer(0, 0, width, height, 0, 0, width, height, mask, GL::NEAREST);
+        }
+
+        gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, self.framebuffer.as_ref());
+        gl.draw_buffers(&draw_buffers(self.colors.len(), None));
+        gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+        gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, None);
+    }
+
+    fn create(&mut self, gl: &GL) -> Result<(), JsValue> {
+        if self.options.colors.iter().any(|format| format.is_float())
+            && gl.get_extension("EXT_color_buffer_float")?.is_none()
+        {
+            return Err(JsValue::from("Float render targets need EXT_color_buffer_float"));
+        }
+        if self.options.layers.is_some() && self.is_multisampled() {
+            return Err(JsValue::from("Layered render targets cannot be multisampled"));
+        }
+
+        self.delete_buffers(gl);
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        let framebuffer = gl.create_framebuffer().ok_or("Could not create framebuffer")?;
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+        for (i, texture) in self.colors.iter().enumerate() {
+            attach(gl, GL::COLOR_ATTACHMENT0 + i as u32, &mut texture.borrow_mut())?;
+        }
+        if let (Some(format), Some(texture)) = (self.options.depth, &self.depth) {
+            attach(gl, depth_attachment(format), &mut texture.borrow_mut())?;
+        } else if let (Some(format), false) = (self.options.depth, self.is_multisampled()) {
+            let renderbuffer = create_renderbuffer(gl, format, 1, width, height)?;
+            gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, depth_attachment(format), GL::RENDERBUFFER, Some(&renderbuffer));
+            self.renderbuffers.push(renderbuffer);
+        }
+        gl.draw_buffers(&draw_buffers(self.colors.len(), None));
+        check_status(gl)?;
+        self.framebuffer = Some(framebuffer);
+
+        if self.is_multisampled() {
+            let framebuffer = gl.create_framebuffer().ok_or("Could not create framebuffer")?;
+            gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+            let samples = self.options.samples;
+            for (i, format) in self.options.colors.iter().enumerate() {
+                let renderbuffer = create_renderbuffer(gl, *format, samples, width, height)?;
+                let attachment = GL::COLOR_ATTACHMENT0 + i as u32;
+                gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, attachment, GL::RENDERBUFFER, Some(&renderbuffer));
+                self.renderbuffers.push(renderbuffer);
+            }
+            if let Some(format) = self.options.depth {
+                let renderbuffer = create_renderbuffer(gl, format, samples, width, height)?;
+                gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, depth_attachment(format), GL::RENDERBUFFER, Some(&renderbuffer));
+                self.renderbuffers.push(renderbuffer);
+            }
+            gl.draw_buffers(&draw_buffers(self.colors.len(), None));
+            check_status(gl)?;
+            self.multisample_framebuffer = Some(framebuffer);
+        }
+
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        Ok(())
+    }
+
+    /// Frees the framebuffers, renderbuffers and textures. The target is
+    /// created again if it is used afterwards, with its content lost.
+    pub fn dispose(&mut self, gl: &GL) {
+        self.delete_buffers(gl);
+        for texture in self.colors.iter().chain(self.depth.iter()) {
+            texture.borrow_mut().dispose(gl);
+        }
+        self.needs_update = true;
+    }
+
+    fn delete_buffers(&mut self, gl: &GL) {
+        gl.delete_framebuffer(self.framebuffer.take().as_ref());
+        gl.delete_framebuffer(self.multisample_framebuffer.take().as_ref());
+        for renderbuffer in self.renderbuffers.drain(..) {
+            gl.delete_renderbuffer(Some(&renderbuffer));
+        }
+    }
+}
+
+fn empty_source(width: u32, height: u32, layers: Option<u32>, format: TextureFormat) -> TextureSource {
+    match layers {
+        Some(layers) => TextureSource::EmptyArray {
+            width,
+            height,
+            layers,
+            format,
+        },
+        None => TextureSource::Empty { width, height, format },
+    }
+}
+
+/// Attaches `texture` to the bound framebuffer, the first layer of arrays.
+fn attach(gl: &GL, attachment: u32, texture: &mut Texture) -> Result<(), JsValue> {
+    if texture.target() == GL::TEXTURE_2D_ARRAY {
+        gl.framebuffer_texture_layer(GL::FRAMEBUFFER, attachment, Some(texture.get_texture(gl)?), 0, 0);
+    } else {
+        gl.framebuffer_texture_2d(GL::FRAMEBUFFER, attachment, GL::TEXTURE_2D, Some(texture.get_texture(gl)?), 0);
+    }
+    Ok(())
+}
+
+fn depth_attachment(format: TextureFormat) -> u32 {
+    if format.has_stencil() {
+        GL::DEPTH_STENCIL_ATTACHMENT
+    } else {
+        GL::DEPTH_ATTACHMENT
+    }
+}
+
+/// The color attachments of a framebuffer, or only `only` with the others
+/// disabled.
+fn draw_buffers(count: usize, only: Option<u32>) -> Array {
+    draw_buffer_attachments(count, only).into_iter().map(JsValue::from).collect()
+}
+
+fn draw_buffer_attachments(count: usize, only: Option<u32>) -> Vec<u32> {
+    (0..count as u32)
+        .map(|i| match only {
+            Some(only) if only != i => GL::NONE,
+            _ => GL::COLOR_ATTACHMENT0 + i,
+        })
+        .collect()
+}
+
+fn create_renderbuffer(
+    gl: &GL,
+    format: TextureFormat,
+    samples: i32,
+    width: i32,
+    height: i32,
+) -> Result<WebGlRenderbuffer, JsValue> {
+    let renderbuffer = gl.create_renderbuffer().ok_or("Could not create renderbuffer")?;
+    gl.bind_renderbuffer(GL::RENDERBUFFER, Some(&renderbuffer));
+    let (internal_format, _, _) = format.gl();
+    if samples > 1 {
+        gl.renderbuffer_storage_multisample(GL::RENDERBUFFER, samples, internal_format, width, height);
+    } else {
+        gl.renderbuffer_storage(GL::RENDERBUFFER, internal_format, width, height);
+    }
+    gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+    Ok(renderbuffer)
+}
+
+fn check_status(gl: &GL) -> Result<(), JsValue> {
+    let status = gl.check_framebuffer_status(GL::FRAMEBUFFER);
+    if status != GL::FRAMEBUFFER_COMPLETE {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        return Err(JsValue::from(format!("Incomplete framebuffer, status 0x{:x}", status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_buffers() {
+        assert!(draw_buffer_attachments(0, None).is_empty());
+        assert_eq!(
+            draw_buffer_attachments(3, None),
+            vec![GL::COLOR_ATTACHMENT0, GL::COLOR_ATTACHMENT1, GL::COLOR_ATTACHMENT2]
+        );
+        assert_eq!(
+            draw_buffer_attachments(3, Some(1)),
+            vec![GL::NONE, GL::COLOR_ATTACHMENT1, GL::NONE]
+        );
+    }
+
+    #[test]
+    fn test_textures() {
+        let mut options = RenderTargetOptions::new();
+        options.colors = vec![TextureFormat::Rgba16F, TextureFormat::Rgba32F];
+        options.depth_texture = true;
+        let target = RenderTarget::new(4, 2, options);
+
+        assert_eq!(target.texture(0).borrow().sampler.min_filter, Filter::Linear);
+        // not filterable without OES_texture_float_linear
+        assert_eq!(target.texture(1).borrow().sampler.min_filter, Filter::Nearest);
+        assert_eq!(target.depth_texture().unwrap().borrow().sampler.mag_filter, Filter::Nearest);
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut options = RenderTargetOptions::new();
+        options.colors = Vec::new();
+        options.depth_texture = true;
+        options.layers = Some(2);
+        let mut target = RenderTarget::new(4, 4, options);
+        target.set_layers(3);
+        target.set_size(8, 8);
+
+        let depth = target.depth_texture().unwrap().borrow();
+        assert_eq!(depth.target(), GL::TEXTURE_2D_ARRAY);
+        match depth.source {
+            TextureSource::EmptyArray { width, layers, .. } => assert_eq!((width, layers), (8, 3)),
+            _ => panic!("expected an array source"),
+        }
+    }
+}
@@ -1,11 +1,71 @@
 use std::{cell::RefCell, rc::Rc};
 
-use cgmath::{perspective, Deg, Matrix4};
+use cgmath::{Deg, Matrix4, SquareMatrix};
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::WebGl2RenderingContext;
 use weblog::console_log;
 
-use super::{graph::Node, object3d::Object3d, scene::Scene};
+#[cfg(debug_assertions)]
+use super::hot_reload;
+use super::{
+    background::BackgroundRenderer,
+    fullscreen::FullscreenTriangle,
+    graph::Node,
+    light::SceneLights,
+    material::BlendMode,
+    object3d::Object3d,
+    postprocessing::Composer,
+    program::ProgramCache,
+    render_queue::RenderQueue,
+    render_state::{RenderState, StateCache},
+    render_target::RenderTarget,
+    scene::Scene,
+    shadow::{CameraFrustum, SceneShadows, ShadowRenderer},
+    uniform::UniformSet,
+};
+
+/// WebGL context creation attributes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ContextOptions {
+    /// Lets the page show through transparent pixels of the canvas.
+    pub alpha: bool,
+    /// Whether the canvas colors are premultiplied by alpha when composited.
+    pub premultiplied_alpha: bool,
+    pub antialias: bool,
+    /// Allocates a stencil buffer for the canvas, needed by materials with a
+    /// `RenderState::stencil` drawing to it.
+    pub stencil: bool,
+}
+
+impl Default for ContextOptions {
+    fn default() -> ContextOptions {
+        ContextOptions::new()
+    }
+}
+
+impl ContextOptions {
+    pub fn new() -> ContextOptions {
+        ContextOptions {
+            alpha: true,
+            premultiplied_alpha: true,
+            antialias: true,
+            stencil: false,
+        }
+    }
+
+    fn to_js(self) -> JsValue {
+        let attributes = js_sys::Object::new();
+        for (name, value) in [
+            ("alpha", self.alpha),
+            ("premultipliedAlpha", self.premultiplied_alpha),
+            ("antialias", self.antialias),
+            ("stencil", self.stencil),
+        ] {
+            js_sys::Reflect::set(&attributes, &JsValue::from(name), &JsValue::from(value)).unwrap();
+        }
+        attributes.into()
+    }
+}
 
 pub struct Renderer {
     pub canvas: web_sys::HtmlCanvasElement,
@@ -13,10 +73,23 @@ pub struct Renderer {
     context: WebGl2RenderingContext,
     width: f64,
     height: f64,
+    background: BackgroundRenderer,
+    // created on first draw, shared by the background and the composer
+    fullscreen: Option<FullscreenTriangle>,
+    programs: RefCell<ProgramCache>,
+    state: RefCell<StateCache>,
+    max_texture_units: usize,
+    shadows: ShadowRenderer,
+    render_target: Option<Rc<RefCell<RenderTarget>>>,
+    composer: Option<Composer>,
 }
 
 impl Renderer {
     pub fn new(canvas_id: &str) -> Renderer {
+        Renderer::new_with_options(canvas_id, ContextOptions::new())
+    }
+
+    pub fn new_with_options(canvas_id: &str, options: ContextOptions) -> Renderer {
         let window = web_sys::window().expect("No global window object");
         let document = window.document().expect("Should have a document on window");
 
@@ -26,21 +99,45 @@ impl Renderer {
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .unwrap();
 
+        Renderer::from_canvas(&canvas, options)
+    }
+
+    pub fn from_canvas(canvas: &web_sys::HtmlCanvasElement, options: ContextOptions) -> Renderer {
+        let canvas = canvas.clone();
         let context = canvas
-            .get_context("webgl2")
+            .get_context_with_context_options("webgl2", &options.to_js())
             .unwrap()
             .unwrap()
             .dyn_into::<WebGl2RenderingContext>()
             .unwrap();
+        let max_texture_units = context
+            .get_parameter(WebGl2RenderingContext::MAX_COMBINED_TEXTURE_IMAGE_UNITS)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as usize;
 
         Renderer {
             canvas,
             context,
             width: 0.0,
             height: 0.0,
+            background: BackgroundRenderer::new(),
+            fullscreen: None,
+            programs: RefCell::new(ProgramCache::new()),
+            state: RefCell::new(StateCache::new()),
+            max_texture_units,
+            shadows: ShadowRenderer::new(),
+            render_target: None,
+            composer: None,
         }
     }
 
+    /// Registers a chunk for `#include "name"` in material shaders. Replacing
+    /// a chunk rebuilds the programs including it, directly or not.
+    pub fn register_shader_chunk(&mut self, name: &str, source: &str) {
+        self.programs.borrow_mut().register_chunk(name, source);
+    }
+
     pub fn set_size(&mut self, width: f64, height: f64) {
         self.canvas.set_width(width as u32);
         self.canvas.set_height(height as u32);
@@ -48,46 +145,210 @@ impl Renderer {
         self.height = height;
 
         self.context.viewport(0, 0, width as i32, height as i32);
+
+        if let Some(composer) = self.composer.as_mut() {
+            composer.set_size(width as u32, height as u32);
+        }
+    }
+
+    /// Post-processes the scene with the passes of `composer`, `None` draws
+    /// the scene directly. The previous composer is disposed.
+    pub fn set_composer(&mut self, composer: Option<Composer>) {
+        if let Some(mut previous) = self.composer.take() {
+            previous.dispose(&self.context);
+        }
+        self.composer = composer;
+        let (width, height) = self.target_size();
+        if let Some(composer) = self.composer.as_mut() {
+            composer.set_size(width as u32, height as u32);
+        }
+    }
+
+    pub fn composer_mut(&mut self) -> Option<&mut Composer> {
+        self.composer.as_mut()
+    }
+
+    /// Makes `draw` render into `target`, or into the canvas with `None`. The
+    /// camera aspect ratio follows the size of the target.
+    pub fn set_render_target(&mut self, target: Option<&Rc<RefCell<RenderTarget>>>) {
+        self.render_target = target.cloned();
+    }
+
+    pub fn get_render_target(&self) -> Option<&Rc<RefCell<RenderTarget>>> {
+        self.render_target.as_ref()
+    }
+
+    /// Size of the current render target, or of the canvas.
+    pub fn target_size(&self) -> (f64, f64) {
+        match &self.render_target {
+            Some(target) => {
+                let target = target.borrow();
+                (target.width() as f64, target.height() as f64)
+            }
+            None => (self.width, self.height),
+        }
+    }
+
+    fn bind_render_target(&self, target: Option<&Rc<RefCell<RenderTarget>>>) -> Result<(), JsValue> {
+        match target {
+            Some(target) => target.borrow_mut().bind(&self.context),
+            None => {
+                self.context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+                self.context.viewport(0, 0, self.width as i32, self.height as i32);
+                Ok(())
+            }
+        }
     }
 
     pub fn create(&mut self) -> Result<(), JsValue> {
-        self.context.enable(WebGl2RenderingContext::DEPTH_TEST);
-        self.context.enable(WebGl2RenderingContext::CULL_FACE);
-        self.context.depth_func(WebGl2RenderingContext::LEQUAL);
+        self.state.borrow_mut().reset(&self.context);
 
         console_log!("Context initialized");
 
         Ok(())
     }
 
-    pub fn draw<'a>(&self, scene: &mut Scene, camera: &'a Matrix4<f32>, _dt: f32) -> Result<(), JsValue> {
+    #[cfg(debug_assertions)]
+    fn apply_reloads(&mut self, scene: &Scene) {
+        for (name, source) in hot_reload::take_pending() {
+            self.register_shader_chunk(&name, &source);
+        }
+
+        let reloads = hot_reload::take_pending_materials();
+        if reloads.is_empty() {
+            return;
+        }
+        Node::traverse(&scene.root, &|node: &Rc<Node<Object3d>>| {
+            if let Some(renderer) = &node.value.borrow().renderer {
+                if let Some(material) = renderer.borrow_mut().material_mut() {
+                    for reload in reloads.iter() {
+                        reload.apply(material);
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn draw(&mut self, scene: &mut Scene, camera: &Matrix4<f32>, _dt: f32) -> Result<(), JsValue> {
+        #[cfg(debug_assertions)]
+        self.apply_reloads(scene);
+
+        if self.fullscreen.is_none() {
+            self.fullscreen = Some(FullscreenTriangle::new(&self.context)?);
+        }
+        let fullscreen = self.fullscreen.as_ref().unwrap();
+
         Node::traverse(&scene.root, &|node: &Rc<Node<Object3d>>| {
             node.value.borrow_mut().update(node);
         });
 
-        let rendering_context  = RenderingContext {
+        let lights = SceneLights::collect(&scene.root);
+        let light_uniforms = lights.uniforms();
+
+        let (width, height) = self.target_size();
+        // the scene goes to the composer target, and the passes to ours
+        let scene_target = match self.composer.as_mut() {
+            Some(composer) if composer.is_active() => {
+                composer.set_size(width as u32, height as u32);
+                Some(composer.scene_target().clone())
+            }
+            _ => self.render_target.clone(),
+        };
+
+        let frustum = CameraFrustum {
+            matrix: *camera,
+            fovy: Deg(45.0).into(),
+            aspect: (width / height) as f32,
+            near: 0.1,
+            far: 100.0,
+        };
+        let shadows = self
+            .shadows
+            .render(&self.context, &self.programs, &scene.root, &lights, &frustum)?;
+        let shadow_uniforms = shadows.uniforms();
+        self.bind_render_target(scene_target.as_ref())?;
+
+        let rendering_context = RenderingContext {
             gl: &self.context,
-            projection_matrix: &perspective(Deg(45.0), (self.width / self.height) as f32, 0.1, 100.0),
+            state: &self.state,
+            max_texture_units: self.max_texture_units,
+            projection_matrix: &frustum.projection(),
             camera_matrix: camera,
+            programs: &self.programs,
+            lights: &lights,
+            light_uniforms: &light_uniforms,
+            shadows: &shadows,
+            shadow_uniforms: &shadow_uniforms,
         };
 
-        self.context.clear_color(0.0, 0.0, 0.0, 1.0);
-        self.context.clear_depth(1.);
-        self.context
-            .clear(WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+        if scene.clear.color {
+            let color = scene.effective_clear_color();
+            self.context.clear_color(color.x, color.y, color.z, color.w);
+        }
+        if scene.clear.depth {
+            self.context.clear_depth(1.);
+        }
+        if scene.clear.stencil {
+            self.context.clear_stencil(0);
+        }
+        let clear_mask = scene.clear.mask();
+        if clear_mask != 0 {
+            self.context.clear(clear_mask);
+        }
 
-        Node::traverse(&scene.root, &|node: &Rc<Node<Object3d>>| {
-            let n = node.value.borrow_mut();
-            if n.renderer.is_some() {
-                n.renderer
-                    .as_ref()
-                    .unwrap()
-                    .borrow_mut()
-                    .render(&n.transform, &rendering_context);
+        if scene.clear.color {
+            self.background.draw(&rendering_context, fullscreen, &scene.background)?;
+        }
+
+        let view_matrix = camera.invert().unwrap_or_else(Matrix4::identity);
+        let queue = RenderQueue::collect(&scene.root, &view_matrix);
+
+        // the first error stops the rendering of the remaining nodes
+        let mut result = Ok(());
+        for item in queue.iter() {
+            let n = item.node.value.borrow();
+            if let Some(renderer) = &n.renderer {
+                result = renderer.borrow_mut().render(&n.transform, &rendering_context);
+                if result.is_err() {
+                    break;
+                }
             }
-        });
+        }
+        // the background, shadow and post-processing passes expect the defaults
+        self.state
+            .borrow_mut()
+            .apply(&self.context, &RenderState::new(), BlendMode::Opaque);
 
-        Ok(())
+        let composing = !ptr_eq(scene_target.as_ref(), self.render_target.as_ref());
+        if let Some(target) = &scene_target {
+            target.borrow_mut().resolve(&self.context);
+        }
+        if composing && result.is_ok() {
+            let composer = self.composer.as_mut().unwrap();
+            composer.render(
+                &self.context,
+                &self.programs,
+                self.max_texture_units,
+                fullscreen,
+                self.render_target.as_ref(),
+            )?;
+            if let Some(target) = &self.render_target {
+                target.borrow_mut().resolve(&self.context);
+            }
+        }
+
+        // release the programs of materials dropped since the last frame
+        self.programs.borrow_mut().collect(&self.context);
+
+        result
+    }
+}
+
+fn ptr_eq(a: Option<&Rc<RefCell<RenderTarget>>>, b: Option<&Rc<RefCell<RenderTarget>>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
     }
 }
 
@@ -117,6 +378,15 @@ impl Renderer {
 
 pub struct RenderingContext<'a, 'b> {
     pub gl: &'a WebGl2RenderingContext,
+    pub programs: &'a RefCell<ProgramCache>,
+    pub state: &'a RefCell<StateCache>,
+    pub max_texture_units: usize,
+    pub lights: &'a SceneLights,
+    /// The uniforms of `lights`.
+    pub light_uniforms: &'a UniformSet,
+    pub shadows: &'a SceneShadows,
+    /// The uniforms of `shadows`, besides the shadow map.
+    pub shadow_uniforms: &'a UniformSet,
     pub projection_matrix: &'b Matrix4<f32>,
     pub camera_matrix: &'b Matrix4<f32>,
 }
@@ -1,20 +1,136 @@
 use std::{cell::RefCell, rc::Rc};
 
+use cgmath::{vec4, Vector4};
+use web_sys::WebGl2RenderingContext;
+
 use super::graph::Node;
 use super::object3d::Object3d;
+use super::texture::{CubeTexture, Texture};
+
+/// Colors are RGBA in [0, 1]. With a transparent canvas (the default
+/// premultiplied alpha context) they must be premultiplied, e.g.
+/// `vec4(0.0, 0.0, 0.0, 0.0)` for a fully transparent background.
+pub enum Background {
+    Color(Vector4<f32>),
+    /// Vertical gradient across the viewport.
+    Gradient { top: Vector4<f32>, bottom: Vector4<f32> },
+    /// A 2D texture stretched over the viewport.
+    Texture(Rc<RefCell<Texture>>),
+    /// A cube map drawn behind everything.
+    Skybox(Rc<RefCell<CubeTexture>>),
+}
+
+impl Background {
+    /// Frees the GPU texture of a `Texture` or `Skybox` background, call it
+    /// before replacing a background that is not shared with anything else.
+    pub fn dispose(&self, gl: &WebGl2RenderingContext) {
+        match self {
+            Background::Texture(texture) => texture.borrow_mut().dispose(gl),
+            Background::Skybox(texture) => texture.borrow_mut().dispose(gl),
+            Background::Color(_) | Background::Gradient { .. } => {}
+        }
+    }
+}
+
+/// Buffers cleared before drawing a scene. Disabling the color clear also
+/// skips the background, to draw a scene over a previous one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClearFlags {
+    pub color: bool,
+    pub depth: bool,
+    pub stencil: bool,
+}
+
+impl ClearFlags {
+    /// The buffer bits for `clear`.
+    pub fn mask(self) -> u32 {
+        let mut mask = 0;
+        if self.color {
+            mask |= WebGl2RenderingContext::COLOR_BUFFER_BIT;
+        }
+        if self.depth {
+            mask |= WebGl2RenderingContext::DEPTH_BUFFER_BIT;
+        }
+        if self.stencil {
+            mask |= WebGl2RenderingContext::STENCIL_BUFFER_BIT;
+        }
+        mask
+    }
+}
 
 pub struct Scene {
     pub root: Rc<Node<Object3d>>,
+    pub background: Background,
+    /// Used for the color clear when the background is not a `Background::Color`.
+    pub clear_color: Vector4<f32>,
+    pub clear: ClearFlags,
+}
+
+impl Default for Scene {
+    fn default() -> Scene {
+        Scene::new()
+    }
 }
 
 impl Scene {
     pub fn new() -> Scene {
         Scene {
             root: Rc::new(Node::new(Object3d::new())),
+            background: Background::Color(vec4(0.0, 0.0, 0.0, 1.0)),
+            clear_color: vec4(0.0, 0.0, 0.0, 1.0),
+            clear: ClearFlags {
+                color: true,
+                depth: true,
+                stencil: true,
+            },
         }
     }
 
     pub fn add_child(&mut self, object: &Rc<Node<Object3d>>) {
         Node::add_child(&self.root, object);
     }
+
+    /// The color the color buffer is cleared with, the background color
+    /// itself for `Background::Color`.
+    pub fn effective_clear_color(&self) -> Vector4<f32> {
+        match &self.background {
+            Background::Color(color) => *color,
+            _ => self.clear_color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type GL = WebGl2RenderingContext;
+
+    #[test]
+    fn test_clear_mask() {
+        let mut scene = Scene::new();
+        assert_eq!(scene.clear.mask(), GL::COLOR_BUFFER_BIT | GL::DEPTH_BUFFER_BIT | GL::STENCIL_BUFFER_BIT);
+
+        scene.clear.color = false;
+        scene.clear.stencil = false;
+        assert_eq!(scene.clear.mask(), GL::DEPTH_BUFFER_BIT);
+
+        scene.clear.depth = false;
+        assert_eq!(scene.clear.mask(), 0);
+    }
+
+    #[test]
+    fn test_effective_clear_color() {
+        let mut scene = Scene::new();
+        scene.clear_color = vec4(0.1, 0.2, 0.3, 1.0);
+
+        scene.background = Background::Color(vec4(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(scene.effective_clear_color(), vec4(1.0, 0.0, 0.0, 1.0));
+
+        scene.background = Background::Gradient {
+            top: vec4(1.0, 1.0, 1.0, 1.0),
+            bottom: vec4(0.0, 0.0, 0.0, 1.0),
+        };
+        assert_eq!(scene.effective_clear_color(), vec4(0.1, 0.2, 0.3, 1.0));
+    }
 }
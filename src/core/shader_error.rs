@@ -0,0 +1,211 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use super::preprocessor::{LineOrigin, Preprocessed};
+
+/// Lines shown before and after an offending line.
+const CONTEXT_LINES: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn from_gl(shader_type: u32) -> ShaderStage {
+        match shader_type {
+            WebGl2RenderingContext::VERTEX_SHADER => ShaderStage::Vertex,
+            _ => ShaderStage::Fragment,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// One entry of a driver info log, such as `ERROR: 0:12: 'foo' : undeclared identifier`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ShaderDiagnostic {
+    pub warning: bool,
+    /// 1-based line in the compiled source, when the driver reported one.
+    pub line: Option<usize>,
+    /// Where that line comes from before preprocessing.
+    pub origin: Option<LineOrigin>,
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ShaderError {
+    /// The GL object could not be created, e.g. after a context loss.
+    Create(String),
+    Preprocess {
+        stage: ShaderStage,
+        message: String,
+    },
+    Compile {
+        stage: ShaderStage,
+        diagnostics: Vec<ShaderDiagnostic>,
+        log: String,
+        source: Preprocessed,
+    },
+    Link {
+        log: String,
+    },
+}
+
+impl ShaderError {
+    pub fn compile(stage: ShaderStage, log: &str, source: &Preprocessed) -> ShaderError {
+        let diagnostics = parse_log(log)
+            .into_iter()
+            .map(|mut d| {
+                d.origin = d.line.and_then(|l| source.origin(l)).cloned();
+                d
+            })
+            .collect();
+
+        ShaderError::Compile {
+            stage,
+            diagnostics,
+            log: log.to_string(),
+            source: source.clone(),
+        }
+    }
+}
+
+/// Parses the `ERROR: <string>:<line>: <message>` entries of an info log,
+/// the format used by ANGLE and the desktop drivers behind WebGL.
+pub fn parse_log(log: &str) -> Vec<ShaderDiagnostic> {
+    log.lines()
+        .filter_map(|entry| {
+            let entry = entry.trim().trim_end_matches('\0');
+            let (warning, rest) = if let Some(rest) = entry.strip_prefix("ERROR:") {
+                (false, rest)
+            } else if let Some(rest) = entry.strip_prefix("WARNING:") {
+                (true, rest)
+            } else {
+                return None;
+            };
+
+            let mut parts = rest.splitn(3, ':');
+            let (string, line, message) = (parts.next(), parts.next(), parts.next());
+            let line = match (string, line, message) {
+                (Some(s), Some(l), Some(_)) if s.trim().parse::<u32>().is_ok() => l.trim().parse::<usize>().ok(),
+                _ => None,
+            };
+            let message = match line {
+                Some(_) => message.unwrap().trim(),
+                None => rest.trim(),
+            };
+
+            Some(ShaderDiagnostic {
+                warning,
+                line,
+                origin: None,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn write_context(f: &mut fmt::Formatter<'_>, source: &Preprocessed, line: usize) -> fmt::Result {
+    let lines: Vec<&str> = source.source.lines().collect();
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (line + CONTEXT_LINES).min(lines.len());
+    for n in first..=last {
+        let marker = if n == line { ">" } else { " " };
+        writeln!(f, "  {} {:4} | {}", marker, n, lines[n - 1])?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Create(message) => write!(f, "{}", message),
+            ShaderError::Preprocess { stage, message } => {
+                write!(f, "Could not preprocess {} shader: {}", stage, message)
+            }
+            ShaderError::Link { log } => write!(f, "Could not link program: {}", log.trim()),
+            ShaderError::Compile {
+                stage,
+                diagnostics,
+                log,
+                source,
+            } => {
+                writeln!(f, "Could not compile {} shader", stage)?;
+                let errors: Vec<&ShaderDiagnostic> = diagnostics.iter().filter(|d| !d.warning).collect();
+                if errors.is_empty() {
+                    return write!(f, "{}", log.trim());
+                }
+                for error in errors {
+                    match (&error.origin, error.line) {
+                        (Some(origin), Some(line)) => {
+                            writeln!(f, "{}: {}", origin, error.message)?;
+                            write_context(f, source, line)?;
+                        }
+                        _ => writeln!(f, "{}", error.message)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<ShaderError> for JsValue {
+    fn from(error: ShaderError) -> JsValue {
+        JsValue::from(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::preprocessor::{preprocess_with_origins, ShaderChunks};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_log() {
+        let log = "WARNING: 0:2: 'x' : unused\nERROR: 0:7: 'foo' : undeclared identifier\nERROR: 1 compilation errors.\0";
+        let diagnostics = parse_log(log);
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0].warning);
+        assert_eq!(diagnostics[1].line, Some(7));
+        assert_eq!(diagnostics[1].message, "'foo' : undeclared identifier");
+        assert_eq!(diagnostics[2].line, None);
+        assert_eq!(diagnostics[2].message, "1 compilation errors.");
+    }
+
+    #[test]
+    fn test_compile_error_through_include() {
+        let mut chunks = ShaderChunks::empty();
+        chunks.register("light", "vec3 light() {\n    return foo;\n}");
+        let source = "#version 300 es\n#include \"light\"\nvoid main(void) {}";
+        let preprocessed = preprocess_with_origins(source, &BTreeMap::new(), &chunks).unwrap();
+
+        let error = ShaderError::compile(
+            ShaderStage::Fragment,
+            "ERROR: 0:3: 'foo' : undeclared identifier",
+            &preprocessed,
+        );
+        match &error {
+            ShaderError::Compile { diagnostics, .. } => {
+                assert_eq!(diagnostics[0].origin, Some(LineOrigin::Chunk("light".to_string(), 2)));
+            }
+            _ => panic!("expected a compile error"),
+        }
+
+        let text = error.to_string();
+        assert!(text.contains("chunk \"light\" line 2: 'foo' : undeclared identifier"));
+        assert!(text.contains(">    3 |     return foo;"));
+    }
+}
@@ -0,0 +1,16 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D inputTexture;
+uniform sampler2D bloomTexture;
+uniform float strength;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec4 color = texture(inputTexture, vUv);
+    FragColor = vec4(color.rgb + texture(bloomTexture, vUv).rgb * strength, color.a);
+}
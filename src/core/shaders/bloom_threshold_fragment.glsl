@@ -0,0 +1,21 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D inputTexture;
+uniform float threshold;
+// width of the soft transition around the threshold
+uniform float knee;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec3 color = texture(inputTexture, vUv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+    FragColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 300 es
+
+precision highp float;
+
+// 9 tap gaussian along `direction`, with linear sampling between texels
+uniform sampler2D inputTexture;
+// size of the input in pixels
+uniform vec2 inputSize;
+uniform vec2 direction;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec2 step = direction / inputSize;
+    vec3 color = texture(inputTexture, vUv).rgb * 0.2270270270;
+    color += texture(inputTexture, vUv + step * 1.3846153846).rgb * 0.3162162162;
+    color += texture(inputTexture, vUv - step * 1.3846153846).rgb * 0.3162162162;
+    color += texture(inputTexture, vUv + step * 3.2307692308).rgb * 0.0702702703;
+    color += texture(inputTexture, vUv - step * 3.2307692308).rgb * 0.0702702703;
+    FragColor = vec4(color, 1.0);
+}
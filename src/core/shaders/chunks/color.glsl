@@ -0,0 +1,9 @@
+// Color space conversions of the lit materials. Color maps are sRGB, lighting
+// is linear and the result is encoded unless LINEAR_OUTPUT is defined.
+vec4 sRGBToLinear(vec4 color) {
+    return vec4(pow(color.rgb, vec3(2.2)), color.a);
+}
+
+vec3 linearToSRGB(vec3 color) {
+    return pow(color, vec3(1.0 / 2.2));
+}
@@ -0,0 +1,139 @@
+// Scene lights in world space, see core/light.rs and core/shadow.rs.
+// Uses the uniforms of the common chunk.
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 8
+#define MAX_SPOT_LIGHTS 4
+
+uniform vec3 ambientLightColor;
+
+uniform int numDirectionalLights;
+uniform vec3 directionalLightDirections[MAX_DIRECTIONAL_LIGHTS];
+uniform vec3 directionalLightColors[MAX_DIRECTIONAL_LIGHTS];
+
+uniform int numPointLights;
+uniform vec3 pointLightPositions[MAX_POINT_LIGHTS];
+uniform vec3 pointLightColors[MAX_POINT_LIGHTS];
+// range, decay
+uniform vec2 pointLightFalloffs[MAX_POINT_LIGHTS];
+
+uniform int numSpotLights;
+uniform vec3 spotLightPositions[MAX_SPOT_LIGHTS];
+uniform vec3 spotLightDirections[MAX_SPOT_LIGHTS];
+uniform vec3 spotLightColors[MAX_SPOT_LIGHTS];
+// range, decay
+uniform vec2 spotLightFalloffs[MAX_SPOT_LIGHTS];
+// cosines of the outer and inner angles
+uniform vec2 spotLightCones[MAX_SPOT_LIGHTS];
+
+#ifdef USE_SHADOWS
+#define MAX_CASCADES 4
+#define MAX_PCF_RADIUS 2
+
+uniform highp sampler2DArrayShadow shadowMap;
+uniform float shadowTexelSize;
+
+uniform int numCascades;
+uniform mat4 cascadeMatrices[MAX_CASCADES];
+// view distance where each cascade ends
+uniform float cascadeSplits[MAX_CASCADES];
+// bias, normal bias, PCF radius, index of the directional light or -1
+uniform vec4 directionalShadow;
+
+uniform mat4 spotShadowMatrices[MAX_SPOT_LIGHTS];
+// bias, normal bias, PCF radius, layer in the shadow map or -1
+uniform vec4 spotShadows[MAX_SPOT_LIGHTS];
+
+// fraction of the light reaching `position` according to the depth map `layer`
+float sampleShadow(mat4 matrix, float layer, vec4 params, vec3 position, vec3 normal) {
+    vec4 clip = matrix * vec4(position + normal * params.y, 1.0);
+    vec3 coord = clip.xyz / clip.w * 0.5 + 0.5;
+    if (coord.z > 1.0 || any(lessThan(coord.xy, vec2(0.0))) || any(greaterThan(coord.xy, vec2(1.0)))) {
+        return 1.0;
+    }
+
+    float depth = coord.z - params.x;
+    int radius = int(params.z);
+    float lit = 0.0;
+    float count = 0.0;
+    for (int x = -MAX_PCF_RADIUS; x <= MAX_PCF_RADIUS; x++) {
+        for (int y = -MAX_PCF_RADIUS; y <= MAX_PCF_RADIUS; y++) {
+            if (abs(x) > radius || abs(y) > radius) continue;
+            vec2 offset = vec2(float(x), float(y)) * shadowTexelSize;
+            lit += texture(shadowMap, vec4(coord.xy + offset, layer, depth));
+            count += 1.0;
+        }
+    }
+    return lit / count;
+}
+#endif
+
+float directionalShadowFactor(int i, vec3 position, vec3 normal) {
+#ifdef USE_SHADOWS
+    if (i == int(directionalShadow.w)) {
+        float depth = -(viewMatrix * vec4(position, 1.0)).z;
+        for (int c = 0; c < MAX_CASCADES; c++) {
+            if (c >= numCascades) break;
+            if (depth <= cascadeSplits[c]) {
+                return sampleShadow(cascadeMatrices[c], float(c), directionalShadow, position, normal);
+            }
+        }
+    }
+#endif
+    return 1.0;
+}
+
+float spotShadowFactor(int i, vec3 position, vec3 normal) {
+#ifdef USE_SHADOWS
+    if (spotShadows[i].w >= 0.0) {
+        return sampleShadow(spotShadowMatrices[i], spotShadows[i].w, spotShadows[i], position, normal);
+    }
+#endif
+    return 1.0;
+}
+
+// the light reaching a surface point
+struct IncidentLight {
+    // towards the light
+    vec3 direction;
+    vec3 color;
+};
+
+float distanceFalloff(float distance, vec2 falloff) {
+    if (falloff.x > 0.0 && falloff.y > 0.0) {
+        return pow(clamp(1.0 - distance / falloff.x, 0.0, 1.0), falloff.y);
+    }
+    return 1.0;
+}
+
+// the point is on the light, any direction will do
+vec3 directionTo(vec3 toLight, float distance) {
+    return distance > 0.0 ? toLight / distance : vec3(0.0, 0.0, 1.0);
+}
+
+// a hard edge when the inner and outer angles are equal
+float coneFalloff(vec2 cone, float cosAngle) {
+    if (cone.y - cone.x < 1e-5) {
+        return step(cone.x, cosAngle);
+    }
+    return smoothstep(cone.x, cone.y, cosAngle);
+}
+
+IncidentLight directionalLight(int i, vec3 position, vec3 normal) {
+    float shadow = directionalShadowFactor(i, position, normal);
+    return IncidentLight(normalize(directionalLightDirections[i]), directionalLightColors[i] * shadow);
+}
+
+IncidentLight pointLight(int i, vec3 position) {
+    vec3 toLight = pointLightPositions[i] - position;
+    float distance = length(toLight);
+    return IncidentLight(directionTo(toLight, distance), pointLightColors[i] * distanceFalloff(distance, pointLightFalloffs[i]));
+}
+
+IncidentLight spotLight(int i, vec3 position, vec3 normal) {
+    vec3 toLight = spotLightPositions[i] - position;
+    float distance = length(toLight);
+    vec3 direction = directionTo(toLight, distance);
+    float cone = coneFalloff(spotLightCones[i], dot(-direction, spotLightDirections[i]));
+    float shadow = spotShadowFactor(i, position, normal);
+    return IncidentLight(direction, spotLightColors[i] * cone * shadow * distanceFalloff(distance, spotLightFalloffs[i]));
+}
@@ -0,0 +1,23 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D inputTexture;
+uniform float brightness;
+uniform float contrast;
+uniform float saturation;
+// multiplies the color, for a white balance or a tint
+uniform vec3 tint;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec4 color = texture(inputTexture, vUv);
+    vec3 rgb = color.rgb * tint + brightness;
+    rgb = (rgb - 0.5) * contrast + 0.5;
+    float luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3(luma), rgb, saturation);
+    FragColor = vec4(rgb, color.a);
+}
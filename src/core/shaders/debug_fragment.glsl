@@ -0,0 +1,34 @@
+#version 300 es
+
+precision highp float;
+
+#include "common"
+
+#ifdef DEBUG_DEPTH
+uniform float near;
+uniform float far;
+#endif
+#ifdef DEBUG_WORLD_POSITION
+uniform float positionScale;
+#endif
+
+in vec3 vWorldPosition;
+in vec3 vNormal;
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+#if defined(DEBUG_NORMAL)
+    FragColor = vec4(normalize(vNormal) * 0.5 + 0.5, 1.0);
+#elif defined(DEBUG_UV)
+    FragColor = vec4(fract(vUv), 0.0, 1.0);
+#elif defined(DEBUG_DEPTH)
+    // linear view distance, white at near and black at far
+    float depth = -(viewMatrix * vec4(vWorldPosition, 1.0)).z;
+    float value = 1.0 - clamp((depth - near) / (far - near), 0.0, 1.0);
+    FragColor = vec4(vec3(value), 1.0);
+#elif defined(DEBUG_WORLD_POSITION)
+    FragColor = vec4(fract(vWorldPosition * positionScale), 1.0);
+#endif
+}
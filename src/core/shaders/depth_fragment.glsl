@@ -0,0 +1,18 @@
+#version 300 es
+
+precision highp float;
+
+#ifdef ALPHA_TEST
+// the alpha map of the material
+uniform sampler2D alphaMap;
+uniform float alphaTest;
+
+in vec2 vUv;
+#endif
+
+// only the depth is written
+void main(void) {
+#ifdef ALPHA_TEST
+    if (texture(alphaMap, vUv).a < alphaTest) discard;
+#endif
+}
@@ -0,0 +1,18 @@
+#version 300 es
+
+in vec3 position;
+#ifdef ALPHA_TEST
+in vec2 uv;
+
+out vec2 vUv;
+#endif
+
+uniform mat4 modelMatrix;
+uniform mat4 lightViewProjection;
+
+void main(void) {
+#ifdef ALPHA_TEST
+    vUv = uv;
+#endif
+    gl_Position = lightViewProjection * modelMatrix * vec4(position, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 300 es
+
+out vec2 vUv;
+
+void main(void) {
+    // full screen triangle on the far plane
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    vUv = position * 0.5 + 0.5;
+
+    gl_Position = vec4(position, 1.0, 1.0);
+}
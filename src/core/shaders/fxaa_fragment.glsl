@@ -0,0 +1,48 @@
+#version 300 es
+
+precision highp float;
+
+// FXAA 3.11 console variant, on the luma of the input
+uniform sampler2D inputTexture;
+uniform vec2 resolution;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+const float EDGE_THRESHOLD_MIN = 0.0312;
+const float EDGE_THRESHOLD_MAX = 0.125;
+const float SUBPIXEL_REDUCE = 1.0 / 128.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main(void) {
+    vec2 texel = 1.0 / resolution;
+    vec4 center = texture(inputTexture, vUv);
+    float lumaM = luma(center.rgb);
+    float lumaNW = luma(texture(inputTexture, vUv + vec2(-1.0, 1.0) * texel).rgb);
+    float lumaNE = luma(texture(inputTexture, vUv + vec2(1.0, 1.0) * texel).rgb);
+    float lumaSW = luma(texture(inputTexture, vUv + vec2(-1.0, -1.0) * texel).rgb);
+    float lumaSE = luma(texture(inputTexture, vUv + vec2(1.0, -1.0) * texel).rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+    if (lumaMax - lumaMin < max(EDGE_THRESHOLD_MIN, lumaMax * EDGE_THRESHOLD_MAX)) {
+        FragColor = center;
+        return;
+    }
+
+    vec2 direction = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * SUBPIXEL_REDUCE, SUBPIXEL_REDUCE);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 a = 0.5 * (texture(inputTexture, vUv - direction / 6.0).rgb + texture(inputTexture, vUv + direction / 6.0).rgb);
+    vec3 b = a * 0.5 + 0.25 * (texture(inputTexture, vUv - direction * 0.5).rgb + texture(inputTexture, vUv + direction * 0.5).rgb);
+    float lumaB = luma(b);
+
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? a : b, center.a);
+}
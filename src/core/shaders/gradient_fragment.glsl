@@ -0,0 +1,14 @@
+#version 300 es
+
+precision highp float;
+
+uniform vec4 topColor;
+uniform vec4 bottomColor;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    FragColor = mix(bottomColor, topColor, vUv.y);
+}
@@ -0,0 +1,161 @@
+#version 300 es
+
+precision highp float;
+
+#include "common"
+#include "lighting"
+#include "color"
+
+// glTF 2.0 metallic-roughness
+uniform vec4 baseColor;
+uniform float metallic;
+uniform float roughness;
+uniform vec3 emissiveColor;
+#ifdef ALPHA_TEST
+uniform float alphaTest;
+#endif
+
+#ifdef USE_BASE_COLOR_MAP
+uniform sampler2D baseColorMap;
+#endif
+#ifdef USE_METALLIC_ROUGHNESS_MAP
+// roughness in green, metalness in blue
+uniform sampler2D metallicRoughnessMap;
+#endif
+#ifdef USE_NORMAL_MAP
+uniform sampler2D normalMap;
+uniform float normalScale;
+#endif
+#ifdef USE_OCCLUSION_MAP
+// occlusion in red
+uniform sampler2D occlusionMap;
+uniform float occlusionStrength;
+#endif
+#ifdef USE_EMISSIVE_MAP
+uniform sampler2D emissiveMap;
+#endif
+
+in vec3 vWorldPosition;
+in vec3 vNormal;
+in vec2 vUv;
+
+out vec4 FragColor;
+
+const float PI = 3.14159265359;
+
+#ifdef USE_NORMAL_MAP
+// tangent frame from screen space derivatives, the geometry has no tangents
+vec3 perturbNormal(vec3 normal, vec3 position, vec2 uv) {
+    vec3 dp1 = dFdx(position);
+    vec3 dp2 = dFdy(position);
+    vec2 duv1 = dFdx(uv);
+    vec2 duv2 = dFdy(uv);
+
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invmax = inversesqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+    mat3 tbn = mat3(tangent * invmax, bitangent * invmax, normal);
+
+    vec3 mapped = texture(normalMap, uv).xyz * 2.0 - 1.0;
+    mapped.xy *= normalScale;
+    return normalize(tbn * mapped);
+}
+#endif
+
+float distributionGGX(float NdotH, float alpha) {
+    float a2 = alpha * alpha;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometrySmith(float NdotV, float NdotL, float alpha) {
+    float k = alpha / 2.0;
+    float gv = NdotV / (NdotV * (1.0 - k) + k);
+    float gl = NdotL / (NdotL * (1.0 - k) + k);
+    return gv * gl;
+}
+
+vec3 fresnelSchlick(float VdotH, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - VdotH, 5.0);
+}
+
+// Cook-Torrance specular plus Lambert diffuse for one light, times N.L
+vec3 brdf(vec3 N, vec3 V, vec3 L, vec3 albedo, float metal, float rough) {
+    vec3 H = normalize(V + L);
+    float NdotL = max(dot(N, L), 0.0);
+    float NdotV = max(dot(N, V), 1e-4);
+    float NdotH = max(dot(N, H), 0.0);
+    float VdotH = max(dot(V, H), 0.0);
+
+    float alpha = rough * rough;
+    vec3 f0 = mix(vec3(0.04), albedo, metal);
+    vec3 F = fresnelSchlick(VdotH, f0);
+    vec3 specular = F * distributionGGX(NdotH, alpha) * geometrySmith(NdotV, NdotL, alpha) / (4.0 * NdotV * NdotL + 1e-4);
+    vec3 diffuse = (1.0 - F) * (1.0 - metal) * albedo / PI;
+
+    return (diffuse + specular) * NdotL;
+}
+
+void main(void) {
+    vec4 color = baseColor;
+#ifdef USE_BASE_COLOR_MAP
+    color *= sRGBToLinear(texture(baseColorMap, vUv));
+#endif
+#ifdef ALPHA_TEST
+    if (color.a < alphaTest) discard;
+#endif
+    float metal = metallic;
+    float rough = roughness;
+#ifdef USE_METALLIC_ROUGHNESS_MAP
+    vec4 metallicRoughness = texture(metallicRoughnessMap, vUv);
+    rough *= metallicRoughness.g;
+    metal *= metallicRoughness.b;
+#endif
+    rough = clamp(rough, 0.04, 1.0);
+    metal = clamp(metal, 0.0, 1.0);
+
+    vec3 N = normalize(vNormal);
+#ifdef USE_NORMAL_MAP
+    N = perturbNormal(N, vWorldPosition, vUv);
+#endif
+    vec3 V = normalize(cameraPosition - vWorldPosition);
+
+    // PI cancels the 1 / PI of the Lambert term, a white light of intensity 1
+    // lights a white surface facing it fully
+    vec3 rgb = vec3(0.0);
+    for (int i = 0; i < MAX_DIRECTIONAL_LIGHTS; i++) {
+        if (i >= numDirectionalLights) break;
+        IncidentLight light = directionalLight(i, vWorldPosition, N);
+        rgb += brdf(N, V, light.direction, color.rgb, metal, rough) * light.color * PI;
+    }
+    for (int i = 0; i < MAX_POINT_LIGHTS; i++) {
+        if (i >= numPointLights) break;
+        IncidentLight light = pointLight(i, vWorldPosition);
+        rgb += brdf(N, V, light.direction, color.rgb, metal, rough) * light.color * PI;
+    }
+    for (int i = 0; i < MAX_SPOT_LIGHTS; i++) {
+        if (i >= numSpotLights) break;
+        IncidentLight light = spotLight(i, vWorldPosition, N);
+        rgb += brdf(N, V, light.direction, color.rgb, metal, rough) * light.color * PI;
+    }
+
+    float occlusion = 1.0;
+#ifdef USE_OCCLUSION_MAP
+    occlusion = mix(1.0, texture(occlusionMap, vUv).r, occlusionStrength);
+#endif
+    rgb += color.rgb * ambientLightColor * occlusion;
+
+    vec3 emissive = emissiveColor;
+#ifdef USE_EMISSIVE_MAP
+    emissive *= sRGBToLinear(texture(emissiveMap, vUv)).rgb;
+#endif
+    rgb += emissive;
+
+#ifndef LINEAR_OUTPUT
+    // drawn for display, a composer tone mapping pass encodes it otherwise
+    rgb = linearToSRGB(clamp(rgb, 0.0, 1.0));
+#endif
+    FragColor = vec4(rgb, color.a);
+}
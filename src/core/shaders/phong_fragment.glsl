@@ -0,0 +1,82 @@
+#version 300 es
+
+precision highp float;
+
+#include "common"
+#include "lighting"
+#include "color"
+
+uniform vec3 diffuseColor;
+uniform vec3 specularColor;
+uniform vec3 emissiveColor;
+uniform float shininess;
+uniform float opacity;
+#ifdef ALPHA_TEST
+uniform float alphaTest;
+#endif
+
+#ifdef USE_DIFFUSE_MAP
+uniform sampler2D diffuseMap;
+#endif
+#ifdef USE_SPECULAR_MAP
+uniform sampler2D specularMap;
+#endif
+
+in vec3 vWorldPosition;
+in vec3 vNormal;
+in vec2 vUv;
+
+out vec4 FragColor;
+
+vec3 shade(IncidentLight light, vec3 normal, vec3 viewDirection, vec3 diffuse, vec3 specular) {
+    float lambert = max(dot(normal, light.direction), 0.0);
+#ifdef USE_BLINN
+    float highlight = max(dot(normal, normalize(light.direction + viewDirection)), 0.0);
+#else
+    float highlight = max(dot(reflect(-light.direction, normal), viewDirection), 0.0);
+#endif
+    // no highlight on faces turned away from the light
+    float specularTerm = lambert > 0.0 ? pow(highlight, shininess) : 0.0;
+
+    return (diffuse * lambert + specular * specularTerm) * light.color;
+}
+
+void main(void) {
+    vec3 diffuse = diffuseColor;
+    float alpha = opacity;
+#ifdef USE_DIFFUSE_MAP
+    vec4 diffuseTexel = sRGBToLinear(texture(diffuseMap, vUv));
+    diffuse *= diffuseTexel.rgb;
+    alpha *= diffuseTexel.a;
+#endif
+#ifdef ALPHA_TEST
+    if (alpha < alphaTest) discard;
+#endif
+    vec3 specular = specularColor;
+#ifdef USE_SPECULAR_MAP
+    specular *= sRGBToLinear(texture(specularMap, vUv)).rgb;
+#endif
+
+    vec3 normal = normalize(vNormal);
+    vec3 viewDirection = normalize(cameraPosition - vWorldPosition);
+
+    vec3 color = diffuse * ambientLightColor + emissiveColor;
+    for (int i = 0; i < MAX_DIRECTIONAL_LIGHTS; i++) {
+        if (i >= numDirectionalLights) break;
+        color += shade(directionalLight(i, vWorldPosition, normal), normal, viewDirection, diffuse, specular);
+    }
+    for (int i = 0; i < MAX_POINT_LIGHTS; i++) {
+        if (i >= numPointLights) break;
+        color += shade(pointLight(i, vWorldPosition), normal, viewDirection, diffuse, specular);
+    }
+    for (int i = 0; i < MAX_SPOT_LIGHTS; i++) {
+        if (i >= numSpotLights) break;
+        color += shade(spotLight(i, vWorldPosition, normal), normal, viewDirection, diffuse, specular);
+    }
+
+#ifndef LINEAR_OUTPUT
+    // drawn for display, a composer tone mapping pass encodes it otherwise
+    color = linearToSRGB(clamp(color, 0.0, 1.0));
+#endif
+    FragColor = vec4(color, alpha);
+}
@@ -0,0 +1,13 @@
+#version 300 es
+
+precision highp float;
+
+uniform samplerCube background;
+
+in vec3 vDirection;
+
+out vec4 FragColor;
+
+void main(void) {
+    FragColor = texture(background, normalize(vDirection));
+}
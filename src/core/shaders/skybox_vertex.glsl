@@ -0,0 +1,15 @@
+#version 300 es
+
+uniform mat4 inverseViewProjection;
+
+out vec3 vDirection;
+
+void main(void) {
+    // full screen triangle on the far plane
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+
+    vec4 direction = inverseViewProjection * vec4(position, 1.0, 1.0);
+    vDirection = direction.xyz / direction.w;
+
+    gl_Position = vec4(position, 1.0, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 300 es
+
+in vec3 position;
+in vec3 normal;
+in vec2 uv;
+
+#include "common"
+
+out vec3 vWorldPosition;
+out vec3 vNormal;
+out vec2 vUv;
+
+void main(void) {
+    vec4 worldPosition = modelMatrix * vec4(position, 1.0);
+    vWorldPosition = worldPosition.xyz;
+
+    mat3 normalMatrix = transpose(inverse(mat3(modelMatrix)));
+    vNormal = normalize(normalMatrix * normal);
+    vUv = uv;
+
+    gl_Position = projectionMatrix * viewMatrix * worldPosition;
+}
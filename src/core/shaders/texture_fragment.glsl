@@ -0,0 +1,13 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D map;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    FragColor = texture(map, vUv);
+}
@@ -0,0 +1,32 @@
+#version 300 es
+
+precision highp float;
+
+// maps HDR linear colors to [0, 1], and encodes them to sRGB with ENCODE_SRGB
+uniform sampler2D inputTexture;
+uniform float exposure;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+vec3 aces(vec3 color) {
+    // Narkowicz's fit of the ACES filmic curve
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main(void) {
+    vec4 color = texture(inputTexture, vUv);
+    vec3 rgb = color.rgb * exposure;
+#if defined(TONE_MAPPING_ACES)
+    rgb = aces(rgb);
+#elif defined(TONE_MAPPING_REINHARD)
+    rgb = rgb / (rgb + 1.0);
+#else
+    rgb = clamp(rgb, 0.0, 1.0);
+#endif
+#ifdef ENCODE_SRGB
+    rgb = pow(rgb, vec3(1.0 / 2.2));
+#endif
+    FragColor = vec4(rgb, color.a);
+}
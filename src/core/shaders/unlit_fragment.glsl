@@ -0,0 +1,27 @@
+#version 300 es
+
+precision highp float;
+
+uniform vec4 color;
+#ifdef ALPHA_TEST
+uniform float alphaTest;
+#endif
+
+#ifdef USE_MAP
+uniform sampler2D map;
+#endif
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec4 result = color;
+#ifdef USE_MAP
+    result *= texture(map, vUv);
+#endif
+#ifdef ALPHA_TEST
+    if (result.a < alphaTest) discard;
+#endif
+    FragColor = result;
+}
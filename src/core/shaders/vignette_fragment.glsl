@@ -0,0 +1,19 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D inputTexture;
+// distance from the center where darkening starts, and how dark the corners get
+uniform float offset;
+uniform float darkness;
+
+in vec2 vUv;
+
+out vec4 FragColor;
+
+void main(void) {
+    vec4 color = texture(inputTexture, vUv);
+    float distance = length(vUv - 0.5) * 1.41421356;
+    float vignette = 1.0 - smoothstep(offset, 1.0, distance) * darkness;
+    FragColor = vec4(color.rgb * vignette, color.a);
+}
@@ -0,0 +1,471 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{
+    ortho, perspective, point3, vec3, vec4, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Transform as _,
+    Vector3, Vector4,
+};
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext as GL, WebGlTexture};
+
+use super::{
+    graph::Node,
+    light::SceneLights,
+    material::Material,
+    object3d::Object3d,
+    program::{ProgramCache, ShaderProgram},
+    render_target::{RenderTarget, RenderTargetOptions},
+    texture::Filter,
+    uniform::{Uniform, UniformSet},
+};
+
+// keep in sync with shaders/chunks/lighting.glsl
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_PCF_RADIUS: u32 = 2;
+
+const DEPTH_VERTEX_SHADER: &str = include_str!("shaders/depth_vertex.glsl");
+const DEPTH_FRAGMENT_SHADER: &str = include_str!("shaders/depth_fragment.glsl");
+
+/// Shadow settings of a directional or spot light. Only the first
+/// directional light with a shadow casts it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LightShadow {
+    /// Width and height of the depth map of each cascade or spot light, the
+    /// largest size among the lights is used for all of them.
+    pub map_size: u32,
+    /// Depth offset against shadow acne, in [0, 1] depth units.
+    pub bias: f32,
+    /// Offset of the receiving point along its normal, in world units.
+    pub normal_bias: f32,
+    /// Samples `(2 * radius + 1)²` texels around the point, 0 gives hard
+    /// edges. At most `MAX_PCF_RADIUS`.
+    pub pcf_radius: u32,
+    /// Number of depth maps splitting the view distance of a directional
+    /// light, at most `MAX_CASCADES`.
+    pub cascades: usize,
+    /// View distance shadowed by a directional light, also how far behind a
+    /// cascade casters are kept. The far plane of a spot light without range.
+    pub distance: f32,
+    /// Blends uniform (0) and logarithmic (1) cascade splits.
+    pub split_lambda: f32,
+    /// Near plane of a spot light.
+    pub near: f32,
+}
+
+impl Default for LightShadow {
+    fn default() -> LightShadow {
+        LightShadow::new()
+    }
+}
+
+impl LightShadow {
+    pub fn new() -> LightShadow {
+        LightShadow {
+            map_size: 1024,
+            bias: 0.0005,
+            normal_bias: 0.02,
+            pcf_radius: 1,
+            cascades: 3,
+            distance: 50.0,
+            split_lambda: 0.7,
+            near: 0.1,
+        }
+    }
+}
+
+/// The perspective of the camera a scene is viewed from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CameraFrustum {
+    /// World matrix of the camera.
+    pub matrix: Matrix4<f32>,
+    pub fovy: Rad<f32>,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl CameraFrustum {
+    pub fn projection(&self) -> Matrix4<f32> {
+        perspective(self.fovy, self.aspect, self.near, self.far)
+    }
+
+    /// World space corners of the slice of the frustum between the view
+    /// distances `near` and `far`.
+    pub fn corners(&self, near: f32, far: f32) -> [Point3<f32>; 8] {
+        let tan = (self.fovy.0 / 2.0).tan();
+        let mut corners = [Point3::origin(); 8];
+        for (i, distance) in [near, far].iter().enumerate() {
+            let height = distance * tan;
+            let width = height * self.aspect;
+            for (j, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter().enumerate() {
+                let point = point3(x * width, y * height, -distance);
+                corners[i * 4 + j] = self.matrix.transform_point(point);
+            }
+        }
+        corners
+    }
+}
+
+/// View distances where each cascade ends, blending uniform and logarithmic
+/// splits of [near, far].
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// View projection of the light covering a cascade. The bounding sphere of
+/// the slice keeps its size while the camera turns, and its center moves by
+/// whole texels, so shadow edges do not shimmer.
+pub fn cascade_matrix(
+    corners: &[Point3<f32>; 8],
+    forward: Vector3<f32>,
+    map_size: u32,
+    extension: f32,
+) -> Matrix4<f32> {
+    let center = Point3::centroid(corners);
+    let radius = corners.iter().map(|c| (c - center).magnitude()).fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let rotation = Matrix4::look_to_rh(Point3::origin(), forward, up_for(forward));
+    let texel = 2.0 * radius / map_size as f32;
+    let c = rotation.transform_point(center);
+    let snapped = vec3((c.x / texel).floor() * texel, (c.y / texel).floor() * texel, c.z);
+    let view = Matrix4::from_translation(-snapped) * rotation;
+
+    // casters between the light and the sphere are kept up to `extension`
+    ortho(-radius, radius, -radius, radius, -(radius + extension), radius) * view
+}
+
+fn up_for(forward: Vector3<f32>) -> Vector3<f32> {
+    if forward.y.abs() > 0.99 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// Everything a depth pass draws with.
+pub struct DepthContext<'a> {
+    pub gl: &'a GL,
+    pub program: &'a ShaderProgram,
+    /// `program` with `ALPHA_TEST` defined, discarding the fragments where
+    /// the `alphaMap` alpha is below `alphaTest`.
+    pub alpha_test_program: &'a ShaderProgram,
+    pub light_view_projection: Matrix4<f32>,
+}
+
+/// The shadows of one frame, uploaded to the materials receiving them.
+pub struct SceneShadows {
+    /// Depth array with the cascades first, then the spot lights.
+    pub texture: Option<WebGlTexture>,
+    pub texel_size: f32,
+    pub cascade_matrices: Vec<Matrix4<f32>>,
+    pub cascade_splits: Vec<f32>,
+    /// Bias, normal bias, PCF radius and index of the directional light,
+    /// -1 without one.
+    pub directional: Vector4<f32>,
+    /// One per spot light.
+    pub spot_matrices: Vec<Matrix4<f32>>,
+    /// Bias, normal bias, PCF radius and layer, -1 for spots without shadow.
+    pub spot_params: Vec<Vector4<f32>>,
+}
+
+impl SceneShadows {
+    pub fn none() -> SceneShadows {
+        SceneShadows {
+            texture: None,
+            texel_size: 0.0,
+            cascade_matrices: Vec::new(),
+            cascade_splits: Vec::new(),
+            directional: vec4(0.0, 0.0, 0.0, -1.0),
+            spot_matrices: Vec::new(),
+            spot_params: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.texture.is_some()
+    }
+
+    /// Binds the shadow map to `unit` for the `shadowMap` sampler. The
+    /// material program must be in use.
+    pub fn bind(&self, gl: &GL, material: &mut Material, unit: u32) {
+        gl.active_texture(GL::TEXTURE0 + unit);
+        gl.bind_texture(GL::TEXTURE_2D_ARRAY, self.texture.as_ref());
+        material.upload_uniform(gl, "shadowMap", &Uniform::Sampler(unit));
+    }
+
+    /// The other uniforms of the shadows in the `lighting` chunk, built once
+    /// per frame and uploaded to every material receiving shadows.
+    pub fn uniforms(&self) -> UniformSet {
+        let mut uniforms = UniformSet::new();
+        uniforms.set("shadowTexelSize", self.texel_size);
+        uniforms.set("numCascades", self.cascade_matrices.len() as i32);
+        uniforms.set("directionalShadow", self.directional);
+
+        // empty arrays are not valid uniform values, the shader skips them anyway
+        if !self.cascade_matrices.is_empty() {
+            uniforms.set("cascadeMatrices", self.cascade_matrices.clone());
+            uniforms.set("cascadeSplits", self.cascade_splits.clone());
+        }
+        if !self.spot_matrices.is_empty() {
+            uniforms.set("spotShadowMatrices", self.spot_matrices.clone());
+            uniforms.set("spotShadows", self.spot_params.clone());
+        }
+        uniforms
+    }
+}
+
+/// Renders the depth maps of the shadow casting lights into the layers of a
+/// depth texture array, sampled with hardware depth comparison.
+pub struct ShadowRenderer {
+    // depth only, one layer per cascade and spot light
+    target: Option<RenderTarget>,
+    // hold the depth programs between frames
+    depth: Material,
+    alpha_test_depth: Material,
+}
+
+impl Default for ShadowRenderer {
+    fn default() -> ShadowRenderer {
+        ShadowRenderer::new()
+    }
+}
+
+impl ShadowRenderer {
+    pub fn new() -> ShadowRenderer {
+        let mut alpha_test_depth = Material::with_shaders(DEPTH_VERTEX_SHADER, DEPTH_FRAGMENT_SHADER);
+        alpha_test_depth.set_define("ALPHA_TEST", "");
+
+        ShadowRenderer {
+            target: None,
+            depth: Material::with_shaders(DEPTH_VERTEX_SHADER, DEPTH_FRAGMENT_SHADER),
+            alpha_test_depth,
+        }
+    }
+
+    /// Leaves the default framebuffer bound, the caller restores its viewport.
+    pub fn render(
+        &mut self,
+        gl: &GL,
+        programs: &RefCell<ProgramCache>,
+        root: &Rc<Node<Object3d>>,
+        lights: &SceneLights,
+        camera: &CameraFrustum,
+    ) -> Result<SceneShadows, JsValue> {
+        let mut shadows = SceneShadows::none();
+        // view projection and size of each layer
+        let mut layers: Vec<(Matrix4<f32>, u32)> = Vec::new();
+
+        let directional = lights
+            .directional_shadows
+            .iter()
+            .enumerate()
+            .find_map(|(i, shadow)| shadow.map(|shadow| (i, shadow)));
+        if let Some((index, shadow)) = directional {
+            let forward = -lights.directional_directions[index].normalize();
+            let far = shadow.distance.min(camera.far);
+            let count = shadow.cascades.clamp(1, MAX_CASCADES);
+            let splits = cascade_splits(camera.near, far, count, shadow.split_lambda);
+
+            let mut near = camera.near;
+            for split in splits.iter() {
+                let corners = camera.corners(near, *split);
+                let matrix = cascade_matrix(&corners, forward, shadow.map_size, shadow.distance);
+                shadows.cascade_matrices.push(matrix);
+                layers.push((matrix, shadow.map_size));
+                near = *split;
+            }
+            shadows.cascade_splits = splits;
+            shadows.directional = params(&shadow, index as f32);
+        }
+
+        for (i, shadow) in lights.spot_shadows.iter().enumerate() {
+            match shadow {
+                Some(shadow) => {
+                    let position = Point3::from_vec(lights.spot_positions[i]);
+                    let forward = lights.spot_directions[i].normalize();
+                    let angle = lights.spot_cones[i].x.acos();
+                    let range = lights.spot_falloffs[i].x;
+                    let far = if range > 0.0 { range } else { shadow.distance };
+
+                    let view = Matrix4::look_to_rh(position, forward, up_for(forward));
+                    let fov = Rad((2.0 * angle).min(3.0));
+                    let matrix = perspective(fov, 1.0, shadow.near, far) * view;
+                    shadows.spot_params.push(params(shadow, layers.len() as f32));
+                    shadows.spot_matrices.push(matrix);
+                    layers.push((matrix, shadow.map_size));
+                }
+                None => {
+                    shadows.spot_params.push(vec4(0.0, 0.0, 0.0, -1.0));
+                    shadows.spot_matrices.push(Matrix4::identity());
+                }
+            }
+        }
+
+        if layers.is_empty() {
+            return Ok(shadows);
+        }
+
+        let size = layers.iter().map(|(_, size)| *size).max().unwrap();
+        let target = allocate(&mut self.target, size, layers.len() as u32);
+
+        let program = self.depth.get_program(gl, &mut programs.borrow_mut())?;
+        let alpha_test_program = self.alpha_test_depth.get_program(gl, &mut programs.borrow_mut())?;
+
+        for (layer, (matrix, _)) in layers.iter().enumerate() {
+            target.bind_layer(gl, layer as u32)?;
+            gl.clear_depth(1.0);
+            gl.clear(GL::DEPTH_BUFFER_BIT);
+
+            let context = DepthContext {
+                gl,
+                program,
+                alpha_test_program,
+                light_view_projection: *matrix,
+            };
+            render_depth(root, &context)?;
+        }
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+        let texture = target.depth_texture().unwrap();
+        shadows.texture = Some(texture.borrow_mut().get_texture(gl)?.clone());
+        shadows.texel_size = 1.0 / size as f32;
+        Ok(shadows)
+    }
+}
+
+/// The shadow map target, created or resized to fit `layers` of `size`.
+fn allocate(target: &mut Option<RenderTarget>, size: u32, layers: u32) -> &mut RenderTarget {
+    let target = target.get_or_insert_with(|| {
+        let mut options = RenderTargetOptions::new();
+        options.colors = Vec::new();
+        options.depth_texture = true;
+        options.layers = Some(layers);
+        let target = RenderTarget::new(size, size, options);
+        {
+            // filtered hardware comparison, smoothing the PCF samples
+            let mut texture = target.depth_texture().unwrap().borrow_mut();
+            texture.sampler.min_filter = Filter::Linear;
+            texture.sampler.mag_filter = Filter::Linear;
+            texture.sampler.compare = true;
+        }
+        target
+    });
+    target.set_size(size, size);
+    target.set_layers(layers);
+    target
+}
+
+fn params(shadow: &LightShadow, index: f32) -> Vector4<f32> {
+    let radius = shadow.pcf_radius.min(MAX_PCF_RADIUS) as f32;
+    vec4(shadow.bias, shadow.normal_bias, radius, index)
+}
+
+/// Draws the shadow casters below `root`, the first error stops the pass.
+fn render_depth(root: &Rc<Node<Object3d>>, context: &DepthContext) -> Result<(), JsValue> {
+    let result = RefCell::new(Ok(()));
+    Node::traverse(root, &|node: &Rc<Node<Object3d>>| {
+        if result.borrow().is_err() {
+            return;
+        }
+        let n = node.value.borrow();
+        if let Some(renderer) = &n.renderer {
+            let res = renderer.borrow_mut().render_depth(&n.transform, context);
+            if res.is_err() {
+                *result.borrow_mut() = res;
+            }
+        }
+    });
+    result.into_inner()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::preprocessor::{preprocess, ShaderChunks};
+    use cgmath::{assert_abs_diff_eq, Deg};
+
+    #[test]
+    fn test_uniforms() {
+        let uniforms = SceneShadows::none().uniforms();
+        assert_eq!(uniforms.get("numCascades"), Some(&Uniform::Int(0)));
+        assert_eq!(uniforms.get("directionalShadow"), Some(&Uniform::Vec4(vec4(0.0, 0.0, 0.0, -1.0))));
+        // no empty arrays
+        assert!(uniforms.get("cascadeMatrices").is_none());
+        assert!(uniforms.get("spotShadows").is_none());
+
+        let mut shadows = SceneShadows::none();
+        shadows.spot_matrices.push(Matrix4::identity());
+        shadows.spot_params.push(vec4(0.0, 0.0, 1.0, 0.0));
+        let uniforms = shadows.uniforms();
+        assert_eq!(uniforms.get("spotShadowMatrices"), Some(&Uniform::Mat4Array(vec![Matrix4::identity()])));
+    }
+
+    #[test]
+    fn test_allocate() {
+        let mut target = None;
+        allocate(&mut target, 512, 2);
+        let target = allocate(&mut target, 1024, 3);
+        assert_eq!((target.width(), target.layers()), (1024, Some(3)));
+
+        let depth = target.depth_texture().unwrap().borrow();
+        assert!(depth.sampler.compare);
+        assert_eq!(depth.sampler.min_filter, Filter::Linear);
+    }
+
+    #[test]
+    fn test_depth_sources_preprocess() {
+        let chunks = ShaderChunks::new();
+        let renderer = ShadowRenderer::new();
+        for material in [&renderer.depth, &renderer.alpha_test_depth] {
+            for source in [material.vertex_shader(), material.fragment_shader()] {
+                let output = preprocess(source, material.defines(), &chunks).unwrap();
+                assert!(output.starts_with("#version 300 es\n"));
+            }
+        }
+        assert!(renderer.alpha_test_depth.defines().contains_key("ALPHA_TEST"));
+    }
+
+    #[test]
+    fn test_cascade_splits() {
+        let splits = cascade_splits(1.0, 100.0, 2, 0.0);
+        assert_abs_diff_eq!(splits[0], 50.5);
+        assert_abs_diff_eq!(splits[1], 100.0);
+
+        let splits = cascade_splits(1.0, 100.0, 2, 1.0);
+        assert_abs_diff_eq!(splits[0], 10.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(splits[1], 100.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_cascade_covers_slice() {
+        let camera = CameraFrustum {
+            matrix: Matrix4::from_translation(vec3(3.0, 2.0, 10.0)),
+            fovy: Deg(45.0).into(),
+            aspect: 1.5,
+            near: 0.1,
+            far: 100.0,
+        };
+        let corners = camera.corners(1.0, 20.0);
+        assert_abs_diff_eq!(corners[0].z, 9.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(corners[4].z, -10.0, epsilon = 1e-5);
+
+        let forward = vec3(-1.0, -2.0, 0.5).normalize();
+        let matrix = cascade_matrix(&corners, forward, 1024, 50.0);
+        for corner in corners.iter() {
+            let p = matrix.transform_point(*corner);
+            assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0 && p.z.abs() <= 1.0, "{:?}", p);
+        }
+
+        // a caster towards the light is still in the depth range
+        let caster = matrix.transform_point(Point3::centroid(&corners) - forward * 30.0);
+        assert!(caster.z.abs() <= 1.0);
+    }
+}
@@ -1,7 +1,7 @@
 use js_sys::Math;
 
 #[allow(dead_code)]
-pub fn cube(width: f32, height: f32, depth: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
+pub fn cube(width: f32, height: f32, depth: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>, Vec<f32>) {
     #[rustfmt::skip]
     let indices = [0, 2, 1, 0, 3, 2, 4, 6, 5, 4, 7, 6, 8, 10, 9, 8, 11, 10, 12, 14, 13, 12, 15, 14, 16, 18, 17, 16, 19, 18, 20, 22, 21, 20, 23, 22];
     #[rustfmt::skip]
@@ -9,6 +9,20 @@ pub fn cube(width: f32, height: f32, depth: f32) -> (Vec<u16>, Vec<f32>, Vec<f32
     #[rustfmt::skip]
     let mut positions = [1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0] as [f32; 72];
 
+    // each face maps the whole texture, seen from outside the cube
+    let mut uvs: Vec<f32> = Vec::new();
+    for (p, n) in positions.chunks(3).zip(normals.chunks(3)) {
+        let (u, v) = if n[2] != 0.0 {
+            (p[0] * n[2], p[1])
+        } else if n[0] != 0.0 {
+            (-p[2] * n[0], p[1])
+        } else {
+            (p[0], -p[2] * n[1])
+        };
+        uvs.push((u + 1.0) / 2.0);
+        uvs.push((v + 1.0) / 2.0);
+    }
+
     let scale = [width / 2.0, height / 2.0, depth / 2.0];
     positions.iter_mut().enumerate().for_each(|(idx, v)| {
         *v = scale[idx % 3] * *v;
@@ -16,13 +30,14 @@ pub fn cube(width: f32, height: f32, depth: f32) -> (Vec<u16>, Vec<f32>, Vec<f32
 
     // console_log!(format!("cube={:?}", positions));
 
-    (indices.to_vec(), positions.to_vec(), normals.to_vec())
+    (indices.to_vec(), positions.to_vec(), normals.to_vec(), uvs)
 }
 
 #[allow(dead_code)]
-pub fn sphere(radius: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
+pub fn sphere(radius: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>, Vec<f32>) {
     let mut positions: Vec<f32> = Vec::new();
     let mut normals: Vec<f32> = Vec::new();
+    let mut uvs: Vec<f32> = Vec::new();
     let mut indices: Vec<u16> = Vec::new();
 
     let height_segments = 24;
@@ -50,6 +65,9 @@ pub fn sphere(radius: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
             normals.push(y);
             normals.push(z);
 
+            uvs.push(u);
+            uvs.push(1.0 - v);
+
             vertices_row.push(index);
             index = index + 1;
         }
@@ -77,7 +95,7 @@ pub fn sphere(radius: f32) -> (Vec<u16>, Vec<f32>, Vec<f32>) {
         }
     }
 
-    (indices, positions, normals)
+    (indices, positions, normals, uvs)
 }
 
 pub fn cos(n: f32) -> f32 {
@@ -0,0 +1,54 @@
+use cgmath::{vec4, SquareMatrix};
+use wasm_bindgen::JsValue;
+
+use super::{
+    fullscreen::FullscreenTriangle,
+    material::Material,
+    renderer::RenderingContext,
+    texture::CubeTexture,
+    uniform::{Uniform, UniformSet},
+};
+
+/// Draws a cube map behind the scene with a single full screen triangle.
+pub struct Skybox {
+    material: Material,
+}
+
+impl Default for Skybox {
+    fn default() -> Skybox {
+        Skybox::new()
+    }
+}
+
+impl Skybox {
+    pub fn new() -> Skybox {
+        Skybox {
+            material: Material::with_shaders(
+                include_str!("shaders/skybox_vertex.glsl"),
+                include_str!("shaders/skybox_fragment.glsl"),
+            ),
+        }
+    }
+
+    /// Must be drawn right after the clear, it does not write depth.
+    pub fn draw(
+        &mut self,
+        context: &RenderingContext,
+        fullscreen: &FullscreenTriangle,
+        texture: &mut CubeTexture,
+    ) -> Result<(), JsValue> {
+        // only the camera rotation matters for a background at infinity
+        let mut view_matrix = context.camera_matrix.invert().ok_or("Camera matrix is not invertible")?;
+        view_matrix.w = vec4(0.0, 0.0, 0.0, 1.0);
+        let inverse_view_projection = (context.projection_matrix * view_matrix)
+            .invert()
+            .ok_or("Projection matrix is not invertible")?;
+
+        // materials only hold 2D textures, the cube map takes unit 0 itself
+        texture.bind(context.gl, 0)?;
+        let mut uniforms = UniformSet::new();
+        uniforms.set("background", Uniform::Sampler(0));
+        uniforms.set("inverseViewProjection", inverse_view_projection);
+        fullscreen.draw(context.gl, context.programs, context.max_texture_units, &mut self.material, &uniforms)
+    }
+}
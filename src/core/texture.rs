@@ -0,0 +1,709 @@
+use std::convert::TryInto;
+
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlImageElement, WebGl2RenderingContext, WebGlTexture};
+
+// From EXT_texture_filter_anisotropic, not exposed by web_sys
+const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FF;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    Repeat,
+    ClampToEdge,
+    MirroredRepeat,
+}
+
+impl Wrap {
+    fn gl(self) -> i32 {
+        (match self {
+            Wrap::Repeat => WebGl2RenderingContext::REPEAT,
+            Wrap::ClampToEdge => WebGl2RenderingContext::CLAMP_TO_EDGE,
+            Wrap::MirroredRepeat => WebGl2RenderingContext::MIRRORED_REPEAT,
+        }) as i32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sampler {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmaps: bool,
+    /// Maximum anisotropy, 1.0 disables it. Clamped to what the driver
+    /// supports and ignored without EXT_texture_filter_anisotropic.
+    pub anisotropy: f32,
+    /// Compares depth textures against the reference depth of the lookup,
+    /// for `sampler2DShadow` and `sampler2DArrayShadow` samplers.
+    pub compare: bool,
+}
+
+impl Default for Sampler {
+    fn default() -> Sampler {
+        Sampler::new()
+    }
+}
+
+impl Sampler {
+    pub fn new() -> Sampler {
+        Sampler {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmaps: true,
+            anisotropy: 1.0,
+            compare: false,
+        }
+    }
+
+    fn min_filter(&self) -> u32 {
+        match (self.min_filter, self.mipmaps) {
+            (Filter::Nearest, false) => WebGl2RenderingContext::NEAREST,
+            (Filter::Linear, false) => WebGl2RenderingContext::LINEAR,
+            (Filter::Nearest, true) => WebGl2RenderingContext::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, true) => WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn mag_filter(&self) -> u32 {
+        match self.mag_filter {
+            Filter::Nearest => WebGl2RenderingContext::NEAREST,
+            Filter::Linear => WebGl2RenderingContext::LINEAR,
+        }
+    }
+
+    pub(crate) fn apply(&self, gl: &WebGl2RenderingContext, target: u32) {
+        gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_S, self.wrap_s.gl());
+        gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_T, self.wrap_t.gl());
+        gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MIN_FILTER, self.min_filter() as i32);
+        gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MAG_FILTER, self.mag_filter() as i32);
+        if self.compare {
+            let mode = WebGl2RenderingContext::COMPARE_REF_TO_TEXTURE as i32;
+            gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_COMPARE_MODE, mode);
+            gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_COMPARE_FUNC, WebGl2RenderingContext::LEQUAL as i32);
+        } else {
+            let mode = WebGl2RenderingContext::NONE as i32;
+            gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_COMPARE_MODE, mode);
+        }
+
+        if self.anisotropy > 1.0 && gl.get_extension("EXT_texture_filter_anisotropic").ok().flatten().is_some() {
+            let max = gl
+                .get_parameter(MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(1.0) as f32;
+            gl.tex_parameterf(target, TEXTURE_MAX_ANISOTROPY_EXT, self.anisotropy.min(max));
+        }
+    }
+}
+
+/// Storage formats of textures without pixel data, such as render target
+/// attachments.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFormat {
+    Rgba8,
+    /// Renderable with EXT_color_buffer_float.
+    Rgba16F,
+    /// Renderable with EXT_color_buffer_float, filterable with
+    /// OES_texture_float_linear.
+    Rgba32F,
+    Depth24,
+    Depth24Stencil8,
+    Depth32F,
+}
+
+impl TextureFormat {
+    /// Internal format, format and type.
+    pub fn gl(self) -> (u32, u32, u32) {
+        type GL = WebGl2RenderingContext;
+        match self {
+            TextureFormat::Rgba8 => (GL::RGBA8, GL::RGBA, GL::UNSIGNED_BYTE),
+            TextureFormat::Rgba16F => (GL::RGBA16F, GL::RGBA, GL::HALF_FLOAT),
+            TextureFormat::Rgba32F => (GL::RGBA32F, GL::RGBA, GL::FLOAT),
+            TextureFormat::Depth24 => (GL::DEPTH_COMPONENT24, GL::DEPTH_COMPONENT, GL::UNSIGNED_INT),
+            TextureFormat::Depth24Stencil8 => (GL::DEPTH24_STENCIL8, GL::DEPTH_STENCIL, GL::UNSIGNED_INT_24_8),
+            TextureFormat::Depth32F => (GL::DEPTH_COMPONENT32F, GL::DEPTH_COMPONENT, GL::FLOAT),
+        }
+    }
+
+    pub fn is_depth(self) -> bool {
+        !matches!(self, TextureFormat::Rgba8 | TextureFormat::Rgba16F | TextureFormat::Rgba32F)
+    }
+
+    pub fn has_stencil(self) -> bool {
+        self == TextureFormat::Depth24Stencil8
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, TextureFormat::Rgba16F | TextureFormat::Rgba32F)
+    }
+
+    /// Whether linear filtering works without extensions. Rgba32F needs
+    /// OES_texture_float_linear, depth formats filter only with
+    /// `Sampler::compare`.
+    pub fn is_filterable(self) -> bool {
+        !self.is_depth() && self != TextureFormat::Rgba32F
+    }
+}
+
+pub enum TextureSource {
+    /// Tightly packed RGBA8 pixels, first row at the bottom.
+    Rgba { width: u32, height: u32, data: Vec<u8> },
+    /// A decoded image. Until it is loaded the texture is a white pixel.
+    Image(HtmlImageElement),
+    /// Uninitialized storage, written by rendering into it.
+    Empty { width: u32, height: u32, format: TextureFormat },
+    /// Uninitialized 2D array storage, bound as `TEXTURE_2D_ARRAY`.
+    EmptyArray {
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+    },
+}
+
+/// A 2D texture, or 2D array texture for `TextureSource::EmptyArray`,
+/// uploaded on first use.
+pub struct Texture {
+    pub source: TextureSource,
+    pub sampler: Sampler,
+    /// Flip images vertically on upload so that uv (0, 0) is their bottom left corner.
+    pub flip_y: bool,
+
+    texture: Option<WebGlTexture>,
+    needs_update: bool,
+    // the placeholder of a loading image is uploaded once
+    placeholder: bool,
+}
+
+impl Texture {
+    pub fn new(source: TextureSource) -> Texture {
+        Texture {
+            source,
+            sampler: Sampler::new(),
+            flip_y: true,
+            texture: None,
+            needs_update: true,
+            placeholder: false,
+        }
+    }
+
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Texture {
+        Texture::new(TextureSource::Rgba { width, height, data })
+    }
+
+    pub fn from_image(image: HtmlImageElement) -> Texture {
+        Texture::new(TextureSource::Image(image))
+    }
+
+    /// Starts loading the image at `url`, the texture is usable right away.
+    pub fn load(url: &str) -> Result<Texture, JsValue> {
+        let image = HtmlImageElement::new()?;
+        image.set_cross_origin(Some("anonymous"));
+        image.set_src(url);
+        Ok(Texture::from_image(image))
+    }
+
+    /// Re-uploads the source and sampler on next use.
+    pub fn set_needs_update(&mut self) {
+        self.needs_update = true;
+    }
+
+    /// Frees the GPU texture, it is created and uploaded again on next use.
+    pub fn dispose(&mut self, gl: &WebGl2RenderingContext) {
+        gl.delete_texture(self.texture.take().as_ref());
+        self.needs_update = true;
+        self.placeholder = false;
+    }
+
+    pub fn get_texture(&mut self, gl: &WebGl2RenderingContext) -> Result<&WebGlTexture, JsValue> {
+        if self.texture.is_none() {
+            self.texture = Some(gl.create_texture().ok_or("Could not create texture")?);
+        }
+
+        if self.needs_update {
+            let ready = self.is_ready();
+            if ready || !self.placeholder {
+                gl.bind_texture(self.target(), self.texture.as_ref());
+                let result = self.upload(gl, ready);
+                gl.bind_texture(self.target(), None);
+                result?;
+                self.placeholder = !ready;
+            }
+            self.needs_update = !ready;
+        }
+
+        Ok(self.texture.as_ref().unwrap())
+    }
+
+    /// `TEXTURE_2D`, or `TEXTURE_2D_ARRAY` for array sources.
+    pub fn target(&self) -> u32 {
+        match self.source {
+            TextureSource::EmptyArray { .. } => WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+            _ => WebGl2RenderingContext::TEXTURE_2D,
+        }
+    }
+
+    /// Whether the source has its pixels, an image may still be loading.
+    fn is_ready(&self) -> bool {
+        match &self.source {
+            TextureSource::Image(image) => image.complete() && image.natural_width() > 0,
+            _ => true,
+        }
+    }
+
+    /// Uploads a placeholder when the source is not `ready`.
+    fn upload(&self, gl: &WebGl2RenderingContext, ready: bool) -> Result<(), JsValue> {
+        let target = self.target();
+        let rgba = WebGl2RenderingContext::RGBA;
+        let bytes = WebGl2RenderingContext::UNSIGNED_BYTE;
+
+        match &self.source {
+            TextureSource::Rgba { width, height, data } => {
+                check_rgba_size(*width, *height, data.len())?;
+                gl.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+                gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target,
+                    0,
+                    rgba as i32,
+                    *width as i32,
+                    *height as i32,
+                    0,
+                    rgba,
+                    bytes,
+                    Some(data),
+                )?;
+            }
+            TextureSource::Image(image) if ready => {
+                gl.pixel_storei(WebGl2RenderingContext::UNPACK_FLIP_Y_WEBGL, self.flip_y as i32);
+                gl.tex_image_2d_with_u32_and_u32_and_html_image_element(target, 0, rgba as i32, rgba, bytes, image)?;
+                gl.pixel_storei(WebGl2RenderingContext::UNPACK_FLIP_Y_WEBGL, 0);
+            }
+            TextureSource::Empty { width, height, format } => {
+                let (internal_format, format, kind) = format.gl();
+                gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target,
+                    0,
+                    internal_format as i32,
+                    *width as i32,
+                    *height as i32,
+                    0,
+                    format,
+                    kind,
+                    None,
+                )?;
+            }
+            TextureSource::EmptyArray {
+                width,
+                height,
+                layers,
+                format,
+            } => {
+                let (internal_format, format, kind) = format.gl();
+                gl.tex_image_3d_with_opt_u8_array(
+                    target,
+                    0,
+                    internal_format as i32,
+                    *width as i32,
+                    *height as i32,
+                    *layers as i32,
+                    0,
+                    format,
+                    kind,
+                    None,
+                )?;
+            }
+            TextureSource::Image(_) => {
+                gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target,
+                    0,
+                    rgba as i32,
+                    1,
+                    1,
+                    0,
+                    rgba,
+                    bytes,
+                    Some(&[255, 255, 255, 255]),
+                )?;
+            }
+        }
+
+        let mut sampler = self.sampler;
+        sampler.mipmaps &= ready;
+        sampler.apply(gl, target);
+        if sampler.mipmaps {
+            gl.generate_mipmap(target);
+        }
+
+        Ok(())
+    }
+
+    /// Binds the texture to texture unit `unit`.
+    pub fn bind(&mut self, gl: &WebGl2RenderingContext, unit: u32) -> Result<(), JsValue> {
+        let target = self.target();
+        let texture = self.get_texture(gl)?;
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        gl.bind_texture(target, Some(texture));
+        Ok(())
+    }
+}
+
+pub enum CubeTextureSource {
+    /// Six square RGBA8 faces in the order +X, -X, +Y, -Y, +Z, -Z.
+    Rgba { size: u32, faces: [Vec<u8>; 6] },
+    /// Six decoded images in the order +X, -X, +Y, -Y, +Z, -Z. Until they are
+    /// all loaded the texture is black.
+    Images([HtmlImageElement; 6]),
+}
+
+/// A cube map texture, uploaded on first use.
+pub struct CubeTexture {
+    pub source: CubeTextureSource,
+    pub sampler: Sampler,
+
+    texture: Option<WebGlTexture>,
+    needs_update: bool,
+    placeholder: bool,
+}
+
+impl CubeTexture {
+    pub fn new(source: CubeTextureSource) -> CubeTexture {
+        let mut sampler = Sampler::new();
+        sampler.wrap_s = Wrap::ClampToEdge;
+        sampler.wrap_t = Wrap::ClampToEdge;
+
+        CubeTexture {
+            source,
+            sampler,
+            texture: None,
+            needs_update: true,
+            placeholder: false,
+        }
+    }
+
+    pub fn from_images(images: [HtmlImageElement; 6]) -> CubeTexture {
+        CubeTexture::new(CubeTextureSource::Images(images))
+    }
+
+    /// Starts loading the six face images, the texture is usable right away.
+    pub fn load(urls: [&str; 6]) -> Result<CubeTexture, JsValue> {
+        let mut images = Vec::new();
+        for url in urls.iter() {
+            let image = HtmlImageElement::new()?;
+            image.set_cross_origin(Some("anonymous"));
+            image.set_src(url);
+            images.push(image);
+        }
+        let images: [HtmlImageElement; 6] = images.try_into().unwrap();
+        Ok(CubeTexture::from_images(images))
+    }
+
+    /// Builds a cube map from an equirectangular panorama of `width` by
+    /// `height` RGBA8 pixels, first row at the top.
+    pub fn from_equirectangular(width: u32, height: u32, data: &[u8], size: u32) -> Result<CubeTexture, JsValue> {
+        Ok(CubeTexture::new(CubeTextureSource::Rgba {
+            size,
+            faces: equirectangular_to_cube(width, height, data, size)?,
+        }))
+    }
+
+    pub fn set_needs_update(&mut self) {
+        self.needs_update = true;
+    }
+
+    /// Frees the GPU texture, it is created and uploaded again on next use.
+    pub fn dispose(&mut self, gl: &WebGl2RenderingContext) {
+        gl.delete_texture(self.texture.take().as_ref());
+        self.needs_update = true;
+        self.placeholder = false;
+    }
+
+
+    pub fn get_texture(&mut self, gl: &WebGl2RenderingContext) -> Result<&WebGlTexture, JsValue> {
+        if self.texture.is_none() {
+            self.texture = Some(gl.create_texture().ok_or("Could not create texture")?);
+        }
+
+        if self.needs_update {
+            let ready = self.is_ready();
+            if ready || !self.placeholder {
+                gl.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, self.texture.as_ref());
+                let result = self.upload(gl, ready);
+                gl.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, None);
+                result?;
+                self.placeholder = !ready;
+            }
+            self.needs_update = !ready;
+        }
+
+        Ok(self.texture.as_ref().unwrap())
+    }
+
+    fn is_ready(&self) -> bool {
+        match &self.source {
+            CubeTextureSource::Images(images) => images.iter().all(|i| i.complete() && i.natural_width() > 0),
+            CubeTextureSource::Rgba { .. } => true,
+        }
+    }
+
+    fn upload(&self, gl: &WebGl2RenderingContext, ready: bool) -> Result<(), JsValue> {
+        let target = WebGl2RenderingContext::TEXTURE_CUBE_MAP;
+        let first_face = WebGl2RenderingContext::TEXTURE_CUBE_MAP_POSITIVE_X;
+        let rgba = WebGl2RenderingContext::RGBA;
+        let bytes = WebGl2RenderingContext::UNSIGNED_BYTE;
+
+        match &self.source {
+            CubeTextureSource::Rgba { size, faces } => {
+                for face in faces.iter() {
+                    check_rgba_size(*size, *size, face.len())?;
+                }
+                gl.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+                for (i, face) in faces.iter().enumerate() {
+                    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        first_face + i as u32,
+                        0,
+                        rgba as i32,
+                        *size as i32,
+                        *size as i32,
+                        0,
+                        rgba,
+                        bytes,
+                        Some(face),
+                    )?;
+                }
+            }
+            CubeTextureSource::Images(images) if ready => {
+                for (i, image) in images.iter().enumerate() {
+                    gl.tex_image_2d_with_u32_and_u32_and_html_image_element(
+                        first_face + i as u32,
+                        0,
+                        rgba as i32,
+                        rgba,
+                        bytes,
+                        image,
+                    )?;
+                }
+            }
+            CubeTextureSource::Images(_) => {
+                for i in 0..6 {
+                    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        first_face + i,
+                        0,
+                        rgba as i32,
+                        1,
+                        1,
+                        0,
+                        rgba,
+                        bytes,
+                        Some(&[0, 0, 0, 255]),
+                    )?;
+                }
+            }
+        }
+
+        let mut sampler = self.sampler;
+        sampler.mipmaps &= ready;
+        sampler.apply(gl, target);
+        gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_R, sampler.wrap_t.gl());
+        if sampler.mipmaps {
+            gl.generate_mipmap(target);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind(&mut self, gl: &WebGl2RenderingContext, unit: u32) -> Result<(), JsValue> {
+        let texture = self.get_texture(gl)?;
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, Some(texture));
+        Ok(())
+    }
+}
+
+/// Checks that `len` bytes are `width` by `height` RGBA8 pixels.
+fn check_rgba_size(width: u32, height: u32, len: usize) -> Result<(), String> {
+    let expected = width as usize * height as usize * 4;
+    if len != expected {
+        return Err(format!(
+            "{}x{} RGBA pixels take {} bytes, got {}",
+            width, height, expected, len
+        ));
+    }
+    Ok(())
+}
+
+/// Resamples an equirectangular panorama into six `size` x `size` cube
+/// faces, in the order and orientation GL expects.
+pub fn equirectangular_to_cube(width: u32, height: u32, data: &[u8], size: u32) -> Result<[Vec<u8>; 6], JsValue> {
+    check_equirectangular(width, height, data.len(), size)?;
+    let mut faces: [Vec<u8>; 6] = Default::default();
+
+    for (index, face) in faces.iter_mut().enumerate() {
+        face.reserve((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = match index {
+                    0 => [1.0, -v, -u],
+                    1 => [-1.0, -v, u],
+                    2 => [u, 1.0, v],
+                    3 => [u, -1.0, -v],
+                    4 => [u, -v, 1.0],
+                    _ => [-u, -v, -1.0],
+                };
+                face.extend_from_slice(&sample_equirectangular(width, height, data, direction));
+            }
+        }
+    }
+
+    Ok(faces)
+}
+
+fn check_equirectangular(width: u32, height: u32, len: usize, size: u32) -> Result<(), String> {
+    if width == 0 || height == 0 || size == 0 {
+        return Err(format!(
+            "Empty panorama or cube face: {}x{} panorama, {} face size",
+            width, height, size
+        ));
+    }
+    check_rgba_size(width, height, len)
+}
+
+/// `data` must hold `width` by `height` RGBA8 pixels, neither being 0.
+fn sample_equirectangular(width: u32, height: u32, data: &[u8], direction: [f32; 3]) -> [u8; 4] {
+    use std::f32::consts::PI;
+
+    let [x, y, z] = direction;
+    let length = (x * x + y * y + z * z).sqrt();
+    let longitude = x.atan2(-z);
+    let latitude = (y / length).asin();
+
+    // bilinear filtering, wrapping horizontally
+    let px = (longitude / (2.0 * PI) + 0.5) * width as f32 - 0.5;
+    let py = ((0.5 - latitude / PI) * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+    let (x0, y0) = (px.floor(), py.floor());
+    let (fx, fy) = (px - x0, py - y0);
+
+    let texel = |tx: f32, ty: f32, channel: usize| -> f32 {
+        let tx = (tx as i64).rem_euclid(width as i64) as usize;
+        let ty = (ty as i64).clamp(0, height as i64 - 1) as usize;
+        data[(ty * width as usize + tx) * 4 + channel] as f32
+    };
+
+    let mut color = [0u8; 4];
+    for (channel, c) in color.iter_mut().enumerate() {
+        let top = texel(x0, y0, channel) * (1.0 - fx) + texel(x0 + 1.0, y0, channel) * fx;
+        let bottom = texel(x0, y0 + 1.0, channel) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0, channel) * fx;
+        *c = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type GL = WebGl2RenderingContext;
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = Sampler::new();
+        assert_eq!(sampler.min_filter(), GL::LINEAR_MIPMAP_LINEAR);
+        sampler.mipmaps = false;
+        assert_eq!(sampler.min_filter(), GL::LINEAR);
+        sampler.min_filter = Filter::Nearest;
+        sampler.mag_filter = Filter::Nearest;
+        assert_eq!(sampler.min_filter(), GL::NEAREST);
+        assert_eq!(sampler.mag_filter(), GL::NEAREST);
+        sampler.mipmaps = true;
+        assert_eq!(sampler.min_filter(), GL::NEAREST_MIPMAP_NEAREST);
+
+        assert_eq!(Wrap::Repeat.gl(), GL::REPEAT as i32);
+        assert_eq!(Wrap::ClampToEdge.gl(), GL::CLAMP_TO_EDGE as i32);
+        assert_eq!(Wrap::MirroredRepeat.gl(), GL::MIRRORED_REPEAT as i32);
+    }
+
+    #[test]
+    fn test_texture_format() {
+        assert_eq!(TextureFormat::Rgba8.gl(), (GL::RGBA8, GL::RGBA, GL::UNSIGNED_BYTE));
+        assert_eq!(TextureFormat::Rgba16F.gl(), (GL::RGBA16F, GL::RGBA, GL::HALF_FLOAT));
+        assert_eq!(TextureFormat::Depth24Stencil8.gl().0, GL::DEPTH24_STENCIL8);
+        assert_eq!(TextureFormat::Depth32F.gl(), (GL::DEPTH_COMPONENT32F, GL::DEPTH_COMPONENT, GL::FLOAT));
+
+        assert!(!TextureFormat::Rgba16F.is_depth());
+        assert!(TextureFormat::Depth24.is_depth());
+        assert!(TextureFormat::Depth24Stencil8.is_depth());
+        assert!(TextureFormat::Depth24Stencil8.has_stencil());
+        assert!(!TextureFormat::Depth32F.has_stencil());
+
+        assert!(TextureFormat::Rgba16F.is_filterable());
+        assert!(!TextureFormat::Rgba32F.is_filterable());
+        assert!(!TextureFormat::Depth24.is_filterable());
+    }
+
+    #[test]
+    fn test_target() {
+        let source = |layers: Option<u32>| {
+            let (width, height, format) = (4, 4, TextureFormat::Depth24);
+            match layers {
+                Some(layers) => TextureSource::EmptyArray {
+                    width,
+                    height,
+                    layers,
+                    format,
+                },
+                None => TextureSource::Empty { width, height, format },
+            }
+        };
+        assert_eq!(Texture::new(source(None)).target(), GL::TEXTURE_2D);
+        assert_eq!(Texture::new(source(Some(1))).target(), GL::TEXTURE_2D_ARRAY);
+    }
+
+    #[test]
+    fn test_check_rgba_size() {
+        assert!(check_rgba_size(2, 3, 24).is_ok());
+        assert!(check_rgba_size(0, 0, 0).is_ok());
+        assert!(check_rgba_size(2, 3, 23).is_err());
+        assert!(check_rgba_size(2, 3, 28).is_err());
+    }
+
+    #[test]
+    fn test_equirectangular_to_cube() {
+        // top half red, bottom half blue
+        let (width, height) = (8, 4);
+        let mut data = Vec::new();
+        for y in 0..height {
+            for _ in 0..width {
+                data.extend_from_slice(if y < height / 2 { &[255, 0, 0, 255] } else { &[0, 0, 255, 255] });
+            }
+        }
+
+        let faces = equirectangular_to_cube(width, height, &data, 4).unwrap();
+        assert!(faces.iter().all(|f| f.len() == 4 * 4 * 4));
+        // +Y is the sky, -Y the ground
+        assert_eq!(&faces[2][0..4], &[255, 0, 0, 255]);
+        assert_eq!(&faces[3][0..4], &[0, 0, 255, 255]);
+        // the top row of a side face looks up
+        assert_eq!(&faces[4][0..4], &[255, 0, 0, 255]);
+        assert_eq!(&faces[4][faces[4].len() - 4..], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_check_equirectangular() {
+        assert!(check_equirectangular(8, 4, 8 * 4 * 4, 4).is_ok());
+        // short data
+        assert!(check_equirectangular(8, 4, 8 * 4 * 4 - 1, 4).is_err());
+        assert!(check_equirectangular(8, 0, 0, 4).is_err());
+        assert!(check_equirectangular(0, 4, 0, 4).is_err());
+        assert!(check_equirectangular(8, 4, 8 * 4 * 4, 0).is_err());
+    }
+}
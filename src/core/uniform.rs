@@ -0,0 +1,164 @@
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use web_sys::{WebGl2RenderingContext, WebGlUniformLocation};
+
+/// A value for a GLSL uniform. Most values convert with `into()`, e.g.
+/// `material.set_uniform("tint", vec4(1.0, 0.5, 0.5, 1.0))`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+    Mat3(Matrix3<f32>),
+    Mat4(Matrix4<f32>),
+    Int(i32),
+    Bool(bool),
+    /// A texture unit for a `sampler2D` or `samplerCube`. Textures set with
+    /// `Material::set_texture` get theirs automatically.
+    Sampler(u32),
+    FloatArray(Vec<f32>),
+    Vec2Array(Vec<Vector2<f32>>),
+    Vec3Array(Vec<Vector3<f32>>),
+    Vec4Array(Vec<Vector4<f32>>),
+    Mat3Array(Vec<Matrix3<f32>>),
+    Mat4Array(Vec<Matrix4<f32>>),
+    IntArray(Vec<i32>),
+    BoolArray(Vec<bool>),
+}
+
+impl Uniform {
+    /// The program must be in use.
+    pub fn upload(&self, gl: &WebGl2RenderingContext, location: Option<&WebGlUniformLocation>) {
+        if location.is_none() {
+            return;
+        }
+
+        match self {
+            Uniform::Float(v) => gl.uniform1f(location, *v),
+            Uniform::Vec2(v) => gl.uniform2fv_with_f32_array(location, v.as_ref() as &[f32; 2]),
+            Uniform::Vec3(v) => gl.uniform3fv_with_f32_array(location, v.as_ref() as &[f32; 3]),
+            Uniform::Vec4(v) => gl.uniform4fv_with_f32_array(location, v.as_ref() as &[f32; 4]),
+            Uniform::Mat3(m) => gl.uniform_matrix3fv_with_f32_array(location, false, m.as_ref() as &[f32; 9]),
+            Uniform::Mat4(m) => gl.uniform_matrix4fv_with_f32_array(location, false, m.as_ref() as &[f32; 16]),
+            Uniform::Int(v) => gl.uniform1i(location, *v),
+            Uniform::Bool(v) => gl.uniform1i(location, *v as i32),
+            Uniform::Sampler(unit) => gl.uniform1i(location, *unit as i32),
+            Uniform::FloatArray(v) => gl.uniform1fv_with_f32_array(location, v),
+            Uniform::Vec2Array(v) => gl.uniform2fv_with_f32_array(location, &flatten(v)),
+            Uniform::Vec3Array(v) => gl.uniform3fv_with_f32_array(location, &flatten(v)),
+            Uniform::Vec4Array(v) => gl.uniform4fv_with_f32_array(location, &flatten(v)),
+            Uniform::Mat3Array(v) => gl.uniform_matrix3fv_with_f32_array(location, false, &flatten(v)),
+            Uniform::Mat4Array(v) => gl.uniform_matrix4fv_with_f32_array(location, false, &flatten(v)),
+            Uniform::IntArray(v) => gl.uniform1iv_with_i32_array(location, v),
+            Uniform::BoolArray(v) => {
+                let values: Vec<i32> = v.iter().map(|b| *b as i32).collect();
+                gl.uniform1iv_with_i32_array(location, &values)
+            }
+        }
+    }
+}
+
+/// Named values shared by every material drawn in a frame, such as the
+/// lights, built once per frame and uploaded with
+/// `Material::upload_uniform_set`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UniformSet {
+    values: Vec<(&'static str, Uniform)>,
+}
+
+impl UniformSet {
+    pub fn new() -> UniformSet {
+        UniformSet { values: Vec::new() }
+    }
+
+    pub fn set<U: Into<Uniform>>(&mut self, name: &'static str, value: U) {
+        let value = value.into();
+        match self.values.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = value,
+            None => self.values.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Uniform> {
+        self.values.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Uniform)> {
+        self.values.iter().map(|(n, v)| (*n, v))
+    }
+}
+
+fn flatten<T: AsRef<[f32; N]>, const N: usize>(values: &[T]) -> Vec<f32> {
+    values.iter().flat_map(|v| v.as_ref().iter().copied()).collect()
+}
+
+macro_rules! impl_from {
+    ($type:ty, $variant:ident) => {
+        impl From<$type> for Uniform {
+            fn from(value: $type) -> Uniform {
+                Uniform::$variant(value)
+            }
+        }
+    };
+}
+
+impl_from!(f32, Float);
+impl_from!(Vector2<f32>, Vec2);
+impl_from!(Vector3<f32>, Vec3);
+impl_from!(Vector4<f32>, Vec4);
+impl_from!(Matrix3<f32>, Mat3);
+impl_from!(Matrix4<f32>, Mat4);
+impl_from!(i32, Int);
+impl_from!(bool, Bool);
+impl_from!(Vec<f32>, FloatArray);
+impl_from!(Vec<Vector2<f32>>, Vec2Array);
+impl_from!(Vec<Vector3<f32>>, Vec3Array);
+impl_from!(Vec<Vector4<f32>>, Vec4Array);
+impl_from!(Vec<Matrix3<f32>>, Mat3Array);
+impl_from!(Vec<Matrix4<f32>>, Mat4Array);
+impl_from!(Vec<i32>, IntArray);
+impl_from!(Vec<bool>, BoolArray);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec2, vec3, SquareMatrix};
+
+    #[test]
+    fn test_uniform_set() {
+        let mut set = UniformSet::new();
+        set.set("count", 1);
+        set.set("color", vec3(1.0, 0.0, 0.0));
+        set.set("count", 2);
+        assert_eq!(set.get("count"), Some(&Uniform::Int(2)));
+        assert_eq!(set.iter().map(|(n, _)| n).collect::<Vec<_>>(), vec!["count", "color"]);
+        assert!(set.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(Uniform::from(1.5), Uniform::Float(1.5));
+        assert_eq!(Uniform::from(2), Uniform::Int(2));
+        assert_eq!(Uniform::from(true), Uniform::Bool(true));
+        assert_eq!(Uniform::from(vec3(1.0, 2.0, 3.0)), Uniform::Vec3(vec3(1.0, 2.0, 3.0)));
+        assert_eq!(Uniform::from(Matrix3::identity()), Uniform::Mat3(Matrix3::identity()));
+        assert_eq!(Uniform::from(vec![true, false]), Uniform::BoolArray(vec![true, false]));
+        assert_eq!(
+            Uniform::from(vec![Matrix3::identity()]),
+            Uniform::Mat3Array(vec![Matrix3::identity()])
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        assert_eq!(flatten(&[vec2(1.0, 2.0), vec2(3.0, 4.0)]), vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(flatten(&[vec3(0.0, 0.0, 0.0); 3]).len(), 9);
+        assert_eq!(flatten(&[Matrix3::<f32>::identity(); 2]).len(), 18);
+
+        // column major
+        let m = flatten(&[Matrix4::from_translation(vec3(1.0, 2.0, 3.0))]);
+        assert_eq!(m.len(), 16);
+        assert_eq!(&m[12..15], &[1.0, 2.0, 3.0]);
+        assert!(flatten::<Vector4<f32>, 4>(&[]).is_empty());
+    }
+}
@@ -1,72 +1,176 @@
 use std::{cell::RefCell, rc::Rc};
 
-use cgmath::{vec3, Deg, Matrix4, Point3, Quaternion, Rotation3, Transform};
-use js_sys::Date;
+use cgmath::{point3, vec3, Deg, Quaternion, Rotation3, Vector3};
 use wasm_bindgen::JsValue;
 use weblog::{console_error, console_log};
 
 use crate::core::{
+    app::{App, AppContext},
+    controls::{fly::FlyControls, orbit::OrbitControls},
     geometry::Geometry,
     graph::Node,
-    material::Material,
+    input::web::EventListeners,
+    light::Light,
+    materials::phong::PhongMaterial,
     object3d::{Mesh, Object3d},
-    renderer::Renderer,
+    postprocessing::{bloom::BloomPass, Composer},
+    renderer::{ContextOptions, Renderer},
     scene::Scene,
+    shadow::LightShadow,
     shapes,
+    texture::TextureFormat,
 };
 
-pub fn example1() -> Result<(Box<dyn FnMut(f64, f64) -> ()>, Box<dyn FnMut() -> ()>), JsValue> {
-    console_log!("Starting example 1");
+pub struct Example1 {
+    scene: Scene,
+    renderer: Option<Renderer>,
+    camera: Rc<Node<Object3d>>,
+    controls: Rc<RefCell<OrbitControls>>,
+    fly_controls: Rc<RefCell<FlyControls>>,
+    // F switches between the orbit and fly controls
+    flying: bool,
+    controls_listeners: Option<EventListeners>,
+    cube0: Rc<Node<Object3d>>,
+    ellapsed: f32,
+}
 
-    // Create cam
+impl Example1 {
+    pub fn new() -> Example1 {
+        // Create scene
+        let mut scene = Scene::new();
+
+        // Add elements
+        let cube0 = create_cube(2.5, String::from("cube0"));
+        scene.add_child(&cube0);
+
+        let cube1 = create_cube(1.5, String::from("cube1"));
+        cube1.value.borrow_mut().transform.position = vec3(5.0, 0.0, 0.0);
+        Node::add_child(&cube0, &cube1);
+
+        // Add lights
+        let sun = Node::new_rc(Object3d::new());
+        let mut sun_light = Light::directional(vec3(1.0, 1.0, 1.0), 1.0);
+        sun_light.shadow = Some(LightShadow::new());
+        sun.value.borrow_mut().light = Some(sun_light);
+        sun.value.borrow_mut().transform.position = vec3(-0.5, 0.5, 0.5);
+        Object3d::look_at(&sun, point3(0.0, 0.0, 0.0), Vector3::unit_y());
+        scene.add_child(&sun);
+
+        let ambient = Node::new_rc(Object3d::new());
+        ambient.value.borrow_mut().light = Some(Light::ambient(vec3(1.0, 1.0, 1.0), 0.05));
+        scene.add_child(&ambient);
+
+        // Create cam
+        let camera = Node::new_rc(Object3d::new());
+        camera.value.borrow_mut().transform.position = vec3(0.0, 7.5, 15.0);
+        Object3d::look_at(&camera, point3(0.0, 0.0, 0.0), Vector3::unit_y());
+
+        let controls = Rc::new(RefCell::new(OrbitControls::new()));
+        controls.borrow_mut().enable_damping = true;
+        controls.borrow_mut().min_distance = 5.0;
+        controls.borrow_mut().max_distance = 50.0;
+
+        Example1 {
+            scene,
+            renderer: None,
+            camera,
+            controls,
+            fly_controls: Rc::new(RefCell::new(FlyControls::new())),
+            flying: false,
+            controls_listeners: None,
+            cube0,
+            ellapsed: 0.0,
+        }
+    }
+
+    fn switch_controls(&mut self) -> Result<(), JsValue> {
+        let canvas = match &self.renderer {
+            Some(renderer) => &renderer.canvas,
+            None => return Ok(()),
+        };
+
+        // the previous listeners are removed first
+        self.controls_listeners = None;
+        self.flying = !self.flying;
+        let listeners = if self.flying {
+            FlyControls::attach(&self.fly_controls, canvas)?
+        } else {
+            self.fly_controls.borrow_mut().reset();
+            if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+                document.exit_pointer_lock();
+            }
+            OrbitControls::attach(&self.controls, canvas)?
+        };
+        self.controls_listeners = Some(listeners);
+        Ok(())
+    }
+}
 
-    // Create scene
-    let mut scene = Scene::new();
+impl App for Example1 {
+    fn init(&mut self, context: &mut AppContext) -> Result<(), JsValue> {
+        console_log!("Starting example 1, F switches between the orbit and fly controls, B toggles bloom");
 
-    // Add elements
-    let cube0 = create_cube(2.5, String::from("cube0"));
-    scene.add_child(&cube0);
+        // Create renderer
+        let mut renderer = Renderer::from_canvas(&context.canvas, ContextOptions::new());
+        renderer.create()?;
+        self.controls_listeners = Some(OrbitControls::attach(&self.controls, &renderer.canvas)?);
+        self.renderer = Some(renderer);
 
-    let cube1 = create_cube(1.5, String::from("cube1"));
-    cube1.value.borrow_mut().transform.position = vec3(5.0, 0.0, 0.0);
-    Node::add_child(&cube0, &cube1);
+        Ok(())
+    }
 
-    // Create renderer
-    let mut renderer = Renderer::new("canvas");
-    renderer.create()?;
-    let renderer_rc = Rc::new(RefCell::new(renderer));
+    fn update(&mut self, context: &mut AppContext, dt: f32) {
+        self.ellapsed = dt;
 
-    let renderer = renderer_rc.clone();
-    let resize_fn = move |width: f64, height: f64| {
-        renderer.borrow_mut().set_size(width, height);
-    };
+        if context.input.borrow().key_pressed("KeyF") {
+            if let Err(err) = self.switch_controls() {
+                console_error!(err);
+            }
+        }
+        if self.flying {
+            self.fly_controls.borrow_mut().update(&self.camera, dt);
+        }
 
-    let mut total_time: f32 = 0.0;
-    let mut time = Date::now();
-    let renderer = renderer_rc.clone();
+        if context.input.borrow().key_pressed("KeyB") {
+            if let Some(renderer) = self.renderer.as_mut() {
+                let composer = match renderer.composer_mut() {
+                    Some(_) => None,
+                    None => Some(bloom_composer()),
+                };
+                renderer.set_composer(composer);
+            }
+        }
+    }
 
-    let camera = Matrix4::from_translation(vec3(0.0, 7.5, 15.0)) * Matrix4::from_angle_x(Deg(-25.0));
+    fn fixed_update(&mut self, _context: &mut AppContext, dt: f32) {
+        //rotate mesh
+        let mut m = self.cube0.value.borrow_mut();
+        m.transform.quaternion = Quaternion::from_angle_y(Deg(dt * 90.0)) * m.transform.quaternion;
+    }
 
-    let update_fn = move || {
-        let newtime = Date::now();
-        let ellapsed = ((newtime - time) / 1000.0) as f32;
-        total_time = total_time + ellapsed;
-        time = newtime;
+    fn render(&mut self, _context: &mut AppContext) -> Result<(), JsValue> {
+        if !self.flying {
+            self.controls.borrow_mut().update(&self.camera);
+        }
+        let camera_matrix = self.camera.value.borrow().transform.matrix_world;
 
-        //rotate mesh
-        {
-            let mut m = cube0.value.borrow_mut();
-            m.transform.quaternion = Quaternion::from_angle_y(Deg(ellapsed * 90.0)) * m.transform.quaternion;
+        match self.renderer.as_mut() {
+            Some(renderer) => renderer.draw(&mut self.scene, &camera_matrix, self.ellapsed),
+            None => Ok(()),
         }
+    }
 
-        // Do rendering
-        let res = renderer.borrow_mut().draw(&mut scene, &camera, ellapsed);
-        if res.is_err() {
-            console_error!(res.unwrap_err());
+    fn resize(&mut self, _context: &mut AppContext, width: f64, height: f64) {
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_size(width, height);
         }
-    };
+    }
+}
 
-    Ok((Box::new(resize_fn), Box::new(update_fn)))
+fn bloom_composer() -> Composer {
+    let mut composer = Composer::new(TextureFormat::Rgba8, 4);
+    composer.add_pass(BloomPass::new(0.8, 0.6));
+    composer
 }
 
 fn create_cube(size: f32, name: String) -> Rc<Node<Object3d>> {
@@ -76,10 +180,13 @@ fn create_cube(size: f32, name: String) -> Rc<Node<Object3d>> {
     geometry.set_indexes(&cube.0);
     geometry.set_positions(&cube.1);
     geometry.set_normals(&cube.2);
+    geometry.set_uvs(&cube.3);
+
+    let mut phong = PhongMaterial::new();
+    phong.diffuse = vec3(0.8, 0.0, 0.2);
+    phong.specular = vec3(0.5, 0.5, 0.5);
 
-    let mut material = Material::new();
-    material.vertex_shader = include_str!("core/shaders/vertex.glsl");
-    material.fragment_shader = include_str!("core/shaders/fragment.glsl");
+    let material = phong.build();
 
     let mesh0 = RefCell::new(Box::new(Mesh::new(material, geometry)));
     let node = Node::new_rc(Object3d::new());
@@ -1,19 +1,12 @@
-use game::example1;
+use game::Example1;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
 use weblog::console_log;
 
+use crate::core::app::Runner;
+
 mod core;
 mod game;
 
-
-fn request_animation_frame(f: &Closure<dyn FnMut()>) {
-    web_sys::window()
-        .unwrap()
-        .request_animation_frame(f.as_ref().unchecked_ref())
-        .expect("should register 'requestAnimationFrame'");
-}
-
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
 // allocator.
 //
@@ -33,32 +26,7 @@ pub fn main_js() -> Result<(), JsValue> {
     // Your code goes here!
     console_log!("Starting wasm webgl2");
 
-    let window = web_sys::window().expect("No global window object");
-
-    let (mut game_resize, mut game_update) = example1()?;
-    game_resize(
-        window.inner_width().unwrap().as_f64().unwrap(),
-        window.inner_height().unwrap().as_f64().unwrap(),
-    );
-
-    let windowc = window.clone();
-    let closure = Closure::wrap(Box::new(move |_event: web_sys::UiEvent| {
-        game_resize(
-            windowc.inner_width().unwrap().as_f64().unwrap(),
-            windowc.inner_height().unwrap().as_f64().unwrap(),
-        );
-    }) as Box<dyn FnMut(_)>);
-    window.add_event_listener_with_event_listener("resize", closure.as_ref().unchecked_ref())?;
-    closure.forget();
-
-    let f = std::rc::Rc::new(std::cell::RefCell::new(None));
-    let g = f.clone();
-    *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-        game_update();
-        request_animation_frame(f.borrow().as_ref().unwrap());
-    }) as Box<dyn FnMut()>));
-
-    request_animation_frame(g.borrow().as_ref().unwrap());
+    Runner::start(Box::new(Example1::new()), "canvas")?;
 
     Ok(())
 }